@@ -1,8 +1,9 @@
 use candid::{CandidType, Deserialize, Nat, Principal};
-use ic_cdk::{caller, query, update};
+use ic_cdk::{caller, id, query, update};
 use ic_cdk_timers::{clear_timer, set_timer};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use icrc_common::Account;
 use serde::Serialize;
 use std::cell::RefCell;
 use std::time::Duration;
@@ -28,6 +29,8 @@ pub enum AuctionStatus {
     Active,
     Ended,
     Cancelled,
+    // Bidding is over but the card could not be delivered; end_auction retries
+    DeliveryFailed,
 }
 
 #[derive(CandidType, Deserialize)]
@@ -35,6 +38,42 @@ pub struct BidArgs {
     pub amount: Nat,
 }
 
+// ICRC-37 transfer_from, as implemented by the player card collection
+#[derive(CandidType, Deserialize)]
+struct TransferFromArg {
+    spender_subaccount: Option<[u8; 32]>,
+    from: Account,
+    to: Account,
+    token_id: Nat,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum TransferFromError {
+    InvalidRecipient,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+enum TransferFromResult {
+    Ok(Nat),
+    Err(TransferFromError),
+}
+
+#[derive(CandidType, Deserialize)]
+struct IsApprovedArg {
+    spender: Account,
+    from_subaccount: Option<[u8; 32]>,
+    token_id: Nat,
+}
+
 thread_local! {
     static AUCTION_DATA: RefCell<Option<AuctionData>> = RefCell::new(None);
     
@@ -50,6 +89,10 @@ thread_local! {
     static END_TIMER: RefCell<Option<ic_cdk_timers::TimerId>> = RefCell::new(None);
 }
 
+fn is_initialized() -> bool {
+    AUCTION_DATA.with(|data| data.borrow().is_some())
+}
+
 #[update]
 async fn init_auction(
     id: Nat,
@@ -60,6 +103,23 @@ async fn init_auction(
     end_time: u64,
     factory_canister: Principal,
 ) -> Result<(), String> {
+    // The factory that created this canister is its controller
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err("Only the auction factory can initialize the auction".to_string());
+    }
+    if is_initialized() {
+        return Err("Auction is already initialized".to_string());
+    }
+
+    // The card is delivered with icrc37_transfer_from, so a listing needs the
+    // seller's approval before it can start
+    check_listing(nft_canister, seller, &token_id).await?;
+
+    // Another init call may have finished while the listing was checked
+    if is_initialized() {
+        return Err("Auction is already initialized".to_string());
+    }
+
     let auction_data = AuctionData {
         id,
         seller,
@@ -80,7 +140,10 @@ async fn init_auction(
     // Set timer to end auction
     let duration = Duration::from_nanos(end_time - ic_cdk::api::time());
     let timer_id = set_timer(duration, || {
-        ic_cdk::spawn(end_auction())
+        // Failures are recorded in the auction status
+        ic_cdk::spawn(async {
+            let _ = end_auction().await;
+        })
     });
     
     END_TIMER.with(|timer| {
@@ -136,21 +199,24 @@ async fn place_bid(args: BidArgs) -> Result<(), String> {
     Ok(())
 }
 
+/// Settles the auction once bidding is over. The auction only becomes `Ended`
+/// after the card reached the winner; a failed delivery leaves it
+/// `DeliveryFailed`, and calling this again retries the transfer.
 #[update]
 async fn end_auction() -> Result<(), String> {
-    let mut auction_data = AUCTION_DATA.with(|data| {
+    let auction_data = AUCTION_DATA.with(|data| {
         data.borrow().clone()
     }).ok_or("Auction not initialized")?;
 
-    if !matches!(auction_data.status, AuctionStatus::Active) {
+    if !matches!(
+        auction_data.status,
+        AuctionStatus::Active | AuctionStatus::DeliveryFailed
+    ) {
         return Err("Auction is not active".to_string());
     }
-
-    auction_data.status = AuctionStatus::Ended;
-    
-    AUCTION_DATA.with(|data| {
-        *data.borrow_mut() = Some(auction_data.clone());
-    });
+    if ic_cdk::api::time() < auction_data.end_time {
+        return Err("Auction has not ended yet".to_string());
+    }
 
     // Clear the timer
     END_TIMER.with(|timer| {
@@ -161,8 +227,13 @@ async fn end_auction() -> Result<(), String> {
 
     // If there's a winning bidder, transfer NFT
     if let Some(winner) = auction_data.highest_bidder {
-        let _ = transfer_nft_to_winner(auction_data.clone(), winner).await;
+        if let Err(err) = transfer_nft_to_winner(auction_data.clone(), winner).await {
+            // A concurrent settlement may have delivered the card meanwhile
+            set_status_unless_ended(AuctionStatus::DeliveryFailed);
+            return Err(err);
+        }
     }
+    set_status_unless_ended(AuctionStatus::Ended);
 
     // Notify factory about auction end
     let _ = ic_cdk::call::<(Nat, AuctionStatus), (Result<(), String>,)>(
@@ -174,24 +245,79 @@ async fn end_auction() -> Result<(), String> {
     Ok(())
 }
 
-async fn transfer_nft_to_winner(auction: AuctionData, winner: Principal) -> Result<(), String> {
-    // Call NFT canister to transfer token
-    // This is a simplified version - in reality, you'd need proper ICRC-7 transfer
-    let transfer_args = (
-        auction.seller,
-        winner,
-        auction.token_id,
-    );
+fn set_status_unless_ended(status: AuctionStatus) {
+    AUCTION_DATA.with(|data| {
+        if let Some(ref mut auction) = *data.borrow_mut() {
+            if !matches!(auction.status, AuctionStatus::Ended) {
+                auction.status = status;
+            }
+        }
+    });
+}
 
-    let _result: Result<(Result<(), String>,), _> = ic_cdk::call(
-        auction.nft_canister,
-        "transfer_from",
-        transfer_args,
-    ).await;
+/// Checks that `seller` owns `token_id` and has approved this canister to
+/// transfer it.
+async fn check_listing(
+    nft_canister: Principal,
+    seller: Principal,
+    token_id: &Nat,
+) -> Result<(), String> {
+    let (owners,): (Vec<Option<Account>>,) =
+        ic_cdk::call(nft_canister, "icrc7_owner_of", (vec![token_id.clone()],))
+            .await
+            .map_err(|(code, message)| {
+                format!("Failed to call NFT canister: {:?} {}", code, message)
+            })?;
+    if owners.into_iter().next().flatten() != Some(Account::from(seller)) {
+        return Err("Seller does not own this card".to_string());
+    }
+
+    let approval = vec![IsApprovedArg {
+        spender: Account::from(id()),
+        from_subaccount: None,
+        token_id: token_id.clone(),
+    }];
+    let (approved,): (Vec<bool>,) = ic_cdk::call(nft_canister, "icrc37_is_approved", (approval,))
+        .await
+        .map_err(|(code, message)| {
+            format!("Failed to call NFT canister: {:?} {}", code, message)
+        })?;
+    if approved.first() != Some(&true) {
+        return Err("Seller has not approved this auction to transfer the card".to_string());
+    }
 
     Ok(())
 }
 
+// The seller must keep this canister approved through icrc37_approve_tokens
+// (or icrc37_approve_collection) until the auction ends.
+async fn transfer_nft_to_winner(auction: AuctionData, winner: Principal) -> Result<(), String> {
+    let transfer_args = vec![TransferFromArg {
+        spender_subaccount: None,
+        from: Account::from(auction.seller),
+        to: Account::from(winner),
+        token_id: auction.token_id,
+        memo: None,
+        created_at_time: None,
+    }];
+
+    let (results,): (Vec<Option<TransferFromResult>>,) = ic_cdk::call(
+        auction.nft_canister,
+        "icrc37_transfer_from",
+        (transfer_args,),
+    )
+    .await
+    .map_err(|(code, message)| format!("Failed to call NFT canister: {:?} {}", code, message))?;
+
+    match results.into_iter().next().flatten() {
+        Some(TransferFromResult::Ok(_)) => Ok(()),
+        Some(TransferFromResult::Err(err)) => {
+            Err(format!("NFT transfer to winner failed: {:?}", err))
+        }
+        None => Err("NFT canister returned no transfer result".to_string()),
+    }
+}
+
 #[update]
 async fn cancel_auction() -> Result<(), String> {
     let caller = caller();
@@ -63,7 +63,30 @@ async fn create_auction(args: CreateAuctionArgs) -> Result<Nat, String> {
 
     // Create new auction canister
     let canister_id = create_auction_canister(auction_id.clone()).await?;
-    
+    let created_at = ic_cdk::api::time();
+    let end_time = created_at + (args.duration_hours * 60 * 60 * 1_000_000_000);
+
+    // Starts the auction; rejected unless the seller owns the card and has
+    // approved the auction canister to deliver it
+    let init_result: Result<(Result<(), String>,), _> = ic_cdk::call(
+        canister_id,
+        "init_auction",
+        (
+            auction_id.clone(),
+            caller,
+            args.nft_canister,
+            args.token_id.clone(),
+            args.starting_price.clone(),
+            end_time,
+            ic_cdk::id(),
+        ),
+    )
+    .await;
+    init_result
+        .map_err(|e| format!("Failed to call auction canister: {:?}", e))?
+        .0
+        .map_err(|e| format!("Failed to initialize auction: {}", e))?;
+
     let auction_info = AuctionInfo {
         id: auction_id.clone(),
         canister_id,
@@ -73,9 +96,9 @@ async fn create_auction(args: CreateAuctionArgs) -> Result<Nat, String> {
         starting_price: args.starting_price,
         current_highest_bid: Nat::from(0u64),
         highest_bidder: None,
-        end_time: ic_cdk::api::time() + (args.duration_hours * 60 * 60 * 1_000_000_000),
+        end_time,
         status: AuctionStatus::Active,
-        created_at: ic_cdk::api::time(),
+        created_at,
     };
 
     AUCTIONS.with(|auctions| {
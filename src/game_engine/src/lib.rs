@@ -62,6 +62,9 @@ pub enum MoveType {
     EndTurn,
 }
 
+// ICRC-1 account used when paying out game token rewards
+pub use icrc_common::Account;
+
 #[derive(CandidType, Deserialize)]
 pub struct CreateMatchArgs {
     pub deck: Vec<Nat>,
@@ -181,6 +184,31 @@ async fn set_active_deck(active_deck: Vec<Nat>) -> Result<(), String> {
     })
 }
 
+/// Called by the player NFT canister after cards are burned, with the owner
+/// each card was burned from, so no deck keeps pointing at a card that no
+/// longer exists.
+#[update]
+fn remove_burned_cards(burned: Vec<(Nat, Principal)>) -> Result<(), String> {
+    let nft_canister = PLAYER_NFT_CANISTER.with(|canister| *canister.borrow())
+        .ok_or("Player NFT canister not set")?;
+    if caller() != nft_canister {
+        return Err("Unauthorized".to_string());
+    }
+
+    PLAYERS.with(|players| {
+        let mut players = players.borrow_mut();
+        for (token_id, owner) in burned {
+            if let Some(mut player) = players.get(&owner) {
+                player.deck.retain(|card_id| *card_id != token_id);
+                player.active_deck.retain(|card_id| *card_id != token_id);
+                players.insert(owner, player);
+            }
+        }
+    });
+
+    Ok(())
+}
+
 async fn verify_card_ownership(player: Principal, card_id: Nat) -> Result<bool, String> {
     let nft_canister = PLAYER_NFT_CANISTER.with(|canister| *canister.borrow())
         .ok_or("Player NFT canister not set")?;
@@ -442,14 +470,29 @@ async fn set_game_token_canister(canister_id: Principal) -> Result<(), String> {
 // Reward system
 #[update]
 async fn reward_player(player_id: Principal, tokens: Nat) -> Result<(), String> {
+    // This canister holds the game token Minter role, so rewards must be gated
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err("Unauthorized".to_string());
+    }
+
     let game_token_canister = GAME_TOKEN_CANISTER.with(|canister| *canister.borrow())
         .ok_or("Game token canister not set")?;
 
-    let _mint_result: Result<(Result<(), String>,), _> = ic_cdk::call(
+    let to = Account {
+        owner: player_id,
+        subaccount: None,
+    };
+
+    let mint_result: Result<(Result<Nat, String>,), _> = ic_cdk::call(
         game_token_canister,
         "mint_game_tokens",
-        (player_id, tokens),
+        (to, tokens),
     ).await;
 
+    mint_result
+        .map_err(|e| format!("Failed to call game token canister: {:?}", e))?
+        .0
+        .map_err(|e| format!("Failed to mint reward: {}", e))?;
+
     Ok(())
 } 
\ No newline at end of file
@@ -0,0 +1,250 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api::{instruction_counter, time};
+use ic_cdk::{caller, query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use serde::Serialize;
+use std::cell::RefCell;
+
+use crate::icrc3::{Operation, Transaction};
+use crate::{
+    apply_transaction, charge_emission_budget, fits_supply_cap, memo_length_error, mutate_state,
+    next_id, record_transaction, require_role, Account, LedgerState, Memory, Role, MEMORY_MANAGER,
+};
+
+// Instruction limit of a single update message
+const UPDATE_INSTRUCTION_LIMIT: u64 = 40_000_000_000;
+
+// Stop minting once a call has used a quarter of the message limit. The
+// budget is only checked between entries and each entry is persisted on its
+// own, so a call overshoots it by at most one mint plus a job header write
+const BATCH_MINT_INSTRUCTION_BUDGET: u64 = UPDATE_INSTRUCTION_LIMIT / 4;
+
+// Entries accepted by a single batch_mint call. All of them are stored before
+// minting starts, so this bounds the work done ahead of the budget check
+const MAX_BATCH_MINT_ENTRIES: usize = 1_000;
+
+pub type BatchMintEntry = (Account, Nat, Option<Vec<u8>>);
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct BatchMintJob {
+    pub id: u64,
+    pub minter: Principal,
+    pub total: u64,
+    // Index of the next entry to mint
+    pub cursor: u64,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct BatchMintProgress {
+    pub batch_id: u64,
+    pub processed: u64,
+    pub total: u64,
+    // Set while entries remain; pass the batch id to resume_batch_mint
+    pub next_cursor: Option<u64>,
+    // Blocks minted by this call, in entry order
+    pub block_indices: Vec<Nat>,
+}
+
+thread_local! {
+    // Unfinished batch mints
+    static BATCH_MINT_JOBS: RefCell<StableBTreeMap<u64, BatchMintJob, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        )
+    );
+
+    // (batch id, entry index) -> entry not minted yet
+    static BATCH_MINT_ENTRIES: RefCell<StableBTreeMap<(u64, u64), BatchMintEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))),
+        )
+    );
+}
+
+/// Mints entries from the job's cursor until done or out of instructions.
+fn run_batch_mint(mut job: BatchMintJob) -> BatchMintProgress {
+    let total = job.total;
+    let mut block_indices = Vec::new();
+
+    while job.cursor < total && instruction_counter() < BATCH_MINT_INSTRUCTION_BUDGET {
+        let (to, amount, memo) = BATCH_MINT_ENTRIES
+            .with(|entries| entries.borrow_mut().remove(&(job.id, job.cursor)))
+            .expect("unminted batch entries are kept until minted");
+
+        // Release the reservation made when the batch was accepted
+        mutate_state(|state| {
+            state.pending_batch_mint = Some(state.pending_batch_mint() - amount.clone())
+        });
+
+        let tx = Transaction {
+            operation: Operation::Mint { to, amount },
+            fee: None,
+            memo,
+            created_at_time: None,
+        };
+        apply_transaction(&tx).expect("minting cannot fail");
+        block_indices.push(record_transaction(tx));
+        job.cursor += 1;
+    }
+
+    let progress = BatchMintProgress {
+        batch_id: job.id,
+        processed: job.cursor,
+        total,
+        next_cursor: (job.cursor < total).then_some(job.cursor),
+        block_indices,
+    };
+
+    BATCH_MINT_JOBS.with(|jobs| {
+        let mut jobs = jobs.borrow_mut();
+        if job.cursor < total {
+            jobs.insert(job.id, job);
+        } else {
+            jobs.remove(&job.id);
+        }
+    });
+
+    progress
+}
+
+/// Validates `entries` and reserves their total against the supply cap and
+/// the current epoch's emission budget. Returns the id of the new batch.
+fn reserve_batch(
+    state: &mut LedgerState,
+    entries: &[BatchMintEntry],
+    now: u64,
+) -> Result<u64, String> {
+    if entries.is_empty() {
+        return Err("Batch is empty".to_string());
+    }
+    if entries.len() > MAX_BATCH_MINT_ENTRIES {
+        return Err(format!(
+            "Batch has {} entries, the maximum is {}",
+            entries.len(),
+            MAX_BATCH_MINT_ENTRIES
+        ));
+    }
+
+    let mut total_amount = Nat::from(0u64);
+    for (index, (_, amount, memo)) in entries.iter().enumerate() {
+        if *amount == Nat::from(0u64) {
+            return Err(format!("Entry {} has a zero amount", index));
+        }
+        if let Some(message) = memo_length_error(memo, state.max_memo_length) {
+            return Err(format!("Entry {}: {}", index, message));
+        }
+        total_amount += amount.clone();
+    }
+    fits_supply_cap(state, &total_amount)?;
+    charge_emission_budget(state, &total_amount, now)?;
+
+    state.pending_batch_mint = Some(state.pending_batch_mint() + total_amount);
+    Ok(next_id(&mut state.next_batch_mint_id))
+}
+
+/// Credits every entry with a mint block. The whole batch is validated and
+/// its total reserved against the supply cap and drawn from the emission
+/// budget up front; if the call runs out of instructions the remaining
+/// entries are kept and minted by `resume_batch_mint`.
+#[update]
+fn batch_mint(entries: Vec<BatchMintEntry>) -> Result<BatchMintProgress, String> {
+    require_role(Role::Minter)?;
+
+    let id = mutate_state(|state| reserve_batch(state, &entries, time()))?;
+
+    let total = entries.len() as u64;
+    BATCH_MINT_ENTRIES.with(|stored| {
+        let mut stored = stored.borrow_mut();
+        for (index, entry) in entries.into_iter().enumerate() {
+            stored.insert((id, index as u64), entry);
+        }
+    });
+
+    Ok(run_batch_mint(BatchMintJob {
+        id,
+        minter: caller(),
+        total,
+        cursor: 0,
+        created_at: time(),
+    }))
+}
+
+#[update]
+fn resume_batch_mint(batch_id: u64) -> Result<BatchMintProgress, String> {
+    require_role(Role::Minter)?;
+
+    let job = BATCH_MINT_JOBS
+        .with(|jobs| jobs.borrow().get(&batch_id))
+        .ok_or("Batch not found or already completed")?;
+
+    if job.minter != caller() {
+        return Err("Only the minter that started the batch can resume it".to_string());
+    }
+
+    Ok(run_batch_mint(job))
+}
+
+#[query]
+fn get_batch_mint_status(batch_id: u64) -> Option<BatchMintProgress> {
+    BATCH_MINT_JOBS.with(|jobs| {
+        jobs.borrow().get(&batch_id).map(|job| BatchMintProgress {
+            batch_id: job.id,
+            processed: job.cursor,
+            total: job.total,
+            next_cursor: Some(job.cursor),
+            block_indices: Vec::new(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EmissionSchedule;
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+
+    fn entries(amounts: &[u64]) -> Vec<BatchMintEntry> {
+        amounts
+            .iter()
+            .map(|amount| {
+                (
+                    Account::from(Principal::anonymous()),
+                    Nat::from(*amount),
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rejects_batches_over_the_entry_cap() {
+        let mut state = LedgerState::default();
+        let oversized = entries(&vec![1; MAX_BATCH_MINT_ENTRIES + 1]);
+
+        assert!(reserve_batch(&mut state, &oversized, NOW).is_err());
+        assert_eq!(state.pending_batch_mint(), Nat::from(0u64));
+        assert!(reserve_batch(&mut state, &oversized[1..], NOW).is_ok());
+    }
+
+    #[test]
+    fn draws_the_batch_total_from_the_emission_budget() {
+        let mut state = LedgerState {
+            emission_schedule: Some(EmissionSchedule {
+                epoch_length_seconds: 3_600,
+                epoch_budget: Nat::from(100u64),
+            }),
+            ..LedgerState::default()
+        };
+
+        assert_eq!(reserve_batch(&mut state, &entries(&[40, 20]), NOW), Ok(0));
+        assert_eq!(state.emission_epoch_minted, Some(Nat::from(60u64)));
+        assert_eq!(state.pending_batch_mint(), Nat::from(60u64));
+
+        assert!(reserve_batch(&mut state, &entries(&[30, 20]), NOW).is_err());
+        assert_eq!(state.emission_epoch_minted, Some(Nat::from(60u64)));
+        assert_eq!(state.pending_batch_mint(), Nat::from(60u64));
+    }
+}
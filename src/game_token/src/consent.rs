@@ -0,0 +1,136 @@
+use candid::{Nat, Principal};
+use ic_cdk::update;
+use icrc_common::icrc21::{
+    consent_info, format_amount, format_timestamp, ConsentInfo, ConsentMessageRequest, Icrc21Error,
+};
+
+use crate::staking::StakeArgs;
+use crate::{
+    minting_account, read_state, transfer_fee, Account, ApproveArgs, TransferArgs, TransferFromArgs,
+};
+
+fn decode<T: candid::CandidType + for<'de> candid::Deserialize<'de>>(
+    request: &ConsentMessageRequest,
+) -> Result<T, Icrc21Error> {
+    candid::decode_one(&request.arg).map_err(|err| Icrc21Error::bad_arg(&request.method, err))
+}
+
+fn tokens(amount: &Nat) -> String {
+    let (decimals, symbol) = read_state(|state| (state.decimals, state.token_symbol.clone()));
+    format!("{} {}", format_amount(amount, decimals), symbol)
+}
+
+fn own_account(subaccount: Option<[u8; 32]>) -> String {
+    match subaccount {
+        Some(subaccount) if subaccount != [0; 32] => {
+            format!("Your subaccount {}", hex_string(&subaccount))
+        }
+        _ => "Your main account".to_string(),
+    }
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Fee shown for a transfer to `to`: transfers to the minting account burn
+/// and are free.
+fn transfer_fee_for(to: &Account, fee: Option<Nat>) -> Nat {
+    if *to == minting_account() {
+        Nat::from(0u64)
+    } else {
+        fee.unwrap_or_else(transfer_fee)
+    }
+}
+
+fn memo_field(fields: &mut Vec<(&str, String)>, memo: &Option<Vec<u8>>) {
+    if let Some(memo) = memo {
+        let memo = String::from_utf8(memo.clone()).unwrap_or_else(|_| hex_string(memo));
+        fields.push(("Memo", memo));
+    }
+}
+
+#[update]
+fn icrc21_canister_call_consent_message(
+    request: ConsentMessageRequest,
+) -> Result<ConsentInfo, Icrc21Error> {
+    let symbol = read_state(|state| state.token_symbol.clone());
+    let utc_offset = request.user_preferences.metadata.utc_offset_minutes;
+
+    let (title, fields) = match request.method.as_str() {
+        "icrc1_transfer" => {
+            let args: TransferArgs = decode(&request)?;
+            let action = if args.to == minting_account() {
+                "Burn"
+            } else {
+                "Send"
+            };
+            let mut fields = vec![
+                ("Amount", tokens(&args.amount)),
+                ("From", own_account(args.from_subaccount)),
+                ("To", args.to.to_string()),
+                ("Fee", tokens(&transfer_fee_for(&args.to, args.fee))),
+            ];
+            memo_field(&mut fields, &args.memo);
+            (format!("{} {}", action, symbol), fields)
+        }
+        "icrc2_approve" => {
+            let args: ApproveArgs = decode(&request)?;
+            let mut fields = vec![
+                ("Spender", args.spender.to_string()),
+                ("Allowance", tokens(&args.amount)),
+                ("From", own_account(args.from_subaccount)),
+                (
+                    "Expires",
+                    args.expires_at.map_or("Never".to_string(), |expires_at| {
+                        format_timestamp(expires_at, utc_offset)
+                    }),
+                ),
+                (
+                    "Approval fee",
+                    tokens(&args.fee.unwrap_or_else(transfer_fee)),
+                ),
+            ];
+            memo_field(&mut fields, &args.memo);
+            (format!("Allow spending of your {}", symbol), fields)
+        }
+        "icrc2_transfer_from" => {
+            let args: TransferFromArgs = decode(&request)?;
+            let mut fields = vec![
+                ("Amount", tokens(&args.amount)),
+                ("From", args.from.to_string()),
+                ("To", args.to.to_string()),
+                ("Fee", tokens(&transfer_fee_for(&args.to, args.fee))),
+            ];
+            memo_field(&mut fields, &args.memo);
+            (format!("Spend approved {}", symbol), fields)
+        }
+        "stake" => {
+            let args: StakeArgs = decode(&request)?;
+            let fields = vec![
+                ("Amount", tokens(&args.amount)),
+                ("From", own_account(args.from_subaccount)),
+                (
+                    "Locked for",
+                    format!("{} days", args.lock_duration_seconds / 86_400),
+                ),
+            ];
+            (format!("Stake {}", symbol), fields)
+        }
+        "create_hold" => {
+            let (account, amount, holder, expires_at): (Account, Nat, Principal, u64) =
+                candid::decode_args(&request.arg)
+                    .map_err(|err| Icrc21Error::bad_arg(&request.method, err))?;
+            let fields = vec![
+                ("Amount", tokens(&amount)),
+                ("From", own_account(account.subaccount)),
+                ("Holder", holder.to_string()),
+                ("Released by", format_timestamp(expires_at, utc_offset)),
+            ];
+            (format!("Reserve {} in escrow", symbol), fields)
+        }
+        method => return Err(Icrc21Error::unsupported(method)),
+    };
+
+    Ok(consent_info(&request.user_preferences, &title, &fields))
+}
@@ -0,0 +1,145 @@
+use candid::{CandidType, Deserialize, Principal};
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{StableBTreeMap, StableLog};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::ops::Bound;
+
+use crate::{require_role, Account, Memory, Role, MEMORY_MANAGER};
+
+// Upper bound on entries returned by a single get_freeze_log call
+const MAX_FREEZE_LOG_ENTRIES_PER_RESPONSE: u64 = 100;
+
+// Upper bound on accounts returned by a single get_frozen_accounts call
+const MAX_FROZEN_ACCOUNTS_PER_RESPONSE: u64 = 100;
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct FreezeRecord {
+    pub reason: String,
+    pub frozen_by: Principal,
+    pub frozen_at: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub enum FreezeAction {
+    Freeze,
+    Unfreeze,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct FreezeEvent {
+    pub account: Account,
+    pub action: FreezeAction,
+    pub reason: String,
+    pub by: Principal,
+    pub timestamp: u64,
+}
+
+thread_local! {
+    static FROZEN_ACCOUNTS: RefCell<StableBTreeMap<Account, FreezeRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))),
+        )
+    );
+
+    // Append-only audit trail of freeze and unfreeze actions (index memory, data memory)
+    static FREEZE_LOG: RefCell<StableLog<FreezeEvent, Memory, Memory>> = RefCell::new(
+        StableLog::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))),
+        ).expect("failed to initialize the freeze log")
+    );
+}
+
+/// Frozen accounts cannot send, approve, burn or lock tokens. They can still
+/// receive them.
+pub fn is_frozen(account: &Account) -> bool {
+    FROZEN_ACCOUNTS.with(|frozen| frozen.borrow().contains_key(account))
+}
+
+fn log_event(account: Account, action: FreezeAction, reason: String) {
+    let event = FreezeEvent {
+        account,
+        action,
+        reason,
+        by: caller(),
+        timestamp: time(),
+    };
+    FREEZE_LOG.with(|log| {
+        log.borrow()
+            .append(&event)
+            .expect("failed to append to the freeze log")
+    });
+}
+
+#[update]
+fn freeze_account(account: Account, reason: String) -> Result<(), String> {
+    require_role(Role::Controller)?;
+
+    if is_frozen(&account) {
+        return Err("Account is already frozen".to_string());
+    }
+
+    let record = FreezeRecord {
+        reason: reason.clone(),
+        frozen_by: caller(),
+        frozen_at: time(),
+    };
+    FROZEN_ACCOUNTS.with(|frozen| frozen.borrow_mut().insert(account.clone(), record));
+    log_event(account, FreezeAction::Freeze, reason);
+    Ok(())
+}
+
+#[update]
+fn unfreeze_account(account: Account, reason: String) -> Result<(), String> {
+    require_role(Role::Controller)?;
+
+    if FROZEN_ACCOUNTS
+        .with(|frozen| frozen.borrow_mut().remove(&account))
+        .is_none()
+    {
+        return Err("Account is not frozen".to_string());
+    }
+    log_event(account, FreezeAction::Unfreeze, reason);
+    Ok(())
+}
+
+#[query]
+fn get_freeze_status(account: Account) -> Option<FreezeRecord> {
+    FROZEN_ACCOUNTS.with(|frozen| frozen.borrow().get(&account))
+}
+
+/// Returns up to `take` frozen accounts in account order, starting after
+/// `prev`.
+#[query]
+fn get_frozen_accounts(prev: Option<Account>, take: Option<u64>) -> Vec<(Account, FreezeRecord)> {
+    let start = match prev {
+        Some(prev) => Bound::Excluded(prev),
+        None => Bound::Unbounded,
+    };
+    let take = take
+        .unwrap_or(MAX_FROZEN_ACCOUNTS_PER_RESPONSE)
+        .min(MAX_FROZEN_ACCOUNTS_PER_RESPONSE);
+
+    FROZEN_ACCOUNTS.with(|frozen| {
+        frozen
+            .borrow()
+            .range((start, Bound::Unbounded))
+            .take(take as usize)
+            .collect()
+    })
+}
+
+/// Returns up to `length` freeze log entries starting at index `start`.
+#[query]
+fn get_freeze_log(start: u64, length: u64) -> Vec<FreezeEvent> {
+    FREEZE_LOG.with(|log| {
+        let log = log.borrow();
+        let end = start
+            .saturating_add(length.min(MAX_FREEZE_LOG_ENTRIES_PER_RESPONSE))
+            .min(log.len());
+        (start..end).filter_map(|index| log.get(index)).collect()
+    })
+}
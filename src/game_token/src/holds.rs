@@ -0,0 +1,207 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use ic_cdk_timers::set_timer;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::icrc3::{Operation, Transaction};
+use crate::{
+    apply_transaction, freeze, mutate_state, next_id, record_transaction, spendable_balance,
+    Account, Memory, MEMORY_MANAGER,
+};
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct Hold {
+    pub id: u64,
+    pub account: Account,
+    pub amount: Nat,
+    // Principal allowed to release or capture the held funds
+    pub holder: Principal,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+thread_local! {
+    static HOLDS: RefCell<StableBTreeMap<u64, Hold, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+        )
+    );
+
+    // (account, hold id)
+    static HOLDS_BY_ACCOUNT: RefCell<StableBTreeMap<(Account, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))),
+        )
+    );
+}
+
+fn holds_of(account: &Account) -> Vec<Hold> {
+    let ids: Vec<u64> = HOLDS_BY_ACCOUNT.with(|index| {
+        index
+            .borrow()
+            .range((account.clone(), 0)..=(account.clone(), u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+
+    HOLDS.with(|holds| {
+        let holds = holds.borrow();
+        ids.into_iter().filter_map(|id| holds.get(&id)).collect()
+    })
+}
+
+/// Tokens of `account` currently reserved by unexpired holds.
+pub fn held_balance(account: &Account) -> Nat {
+    let now = time();
+    holds_of(account)
+        .into_iter()
+        .filter(|hold| hold.expires_at > now)
+        .fold(Nat::from(0u64), |total, hold| total + hold.amount)
+}
+
+fn remove_hold(hold: &Hold) {
+    HOLDS.with(|holds| holds.borrow_mut().remove(&hold.id));
+    HOLDS_BY_ACCOUNT.with(|index| index.borrow_mut().remove(&(hold.account.clone(), hold.id)));
+}
+
+/// Releases `hold_id` if it is still active and past its expiry.
+fn expire_hold(hold_id: u64) {
+    if let Some(hold) = HOLDS.with(|holds| holds.borrow().get(&hold_id)) {
+        if hold.expires_at <= time() {
+            remove_hold(&hold);
+        }
+    }
+}
+
+fn schedule_expiry(hold_id: u64, expires_at: u64) {
+    let delay = Duration::from_nanos(expires_at.saturating_sub(time()));
+    set_timer(delay, move || expire_hold(hold_id));
+}
+
+/// Re-arms expiry timers, which do not survive upgrades.
+pub fn schedule_expiries() {
+    let holds: Vec<(u64, u64)> = HOLDS.with(|holds| {
+        holds
+            .borrow()
+            .iter()
+            .map(|(id, hold)| (id, hold.expires_at))
+            .collect()
+    });
+    for (hold_id, expires_at) in holds {
+        schedule_expiry(hold_id, expires_at);
+    }
+}
+
+fn active_hold(hold_id: u64) -> Result<Hold, String> {
+    let hold = HOLDS
+        .with(|holds| holds.borrow().get(&hold_id))
+        .ok_or("Hold not found")?;
+
+    if hold.holder != caller() {
+        return Err("Only the holder can settle this hold".to_string());
+    }
+    if hold.expires_at <= time() {
+        expire_hold(hold_id);
+        return Err("Hold has expired".to_string());
+    }
+
+    Ok(hold)
+}
+
+/// Reserves `amount` of the caller's `account` for `holder` until
+/// `expires_at` (nanoseconds since the epoch). Held funds stay in the account
+/// but cannot be spent until the hold is released, captured or expires.
+#[update]
+fn create_hold(
+    account: Account,
+    amount: Nat,
+    holder: Principal,
+    expires_at: u64,
+) -> Result<u64, String> {
+    if account.owner != caller() {
+        return Err("Unauthorized".to_string());
+    }
+    if freeze::is_frozen(&account) {
+        return Err("Account is frozen".to_string());
+    }
+    if amount == Nat::from(0u64) {
+        return Err("Hold amount must be positive".to_string());
+    }
+
+    let now = time();
+    if expires_at <= now {
+        return Err("Hold expiry must be in the future".to_string());
+    }
+
+    let spendable = spendable_balance(&account);
+    if spendable < amount {
+        return Err(format!("Insufficient spendable balance: {}", spendable));
+    }
+
+    let id = mutate_state(|state| next_id(&mut state.next_hold_id));
+    let hold = Hold {
+        id,
+        account: account.clone(),
+        amount,
+        holder,
+        created_at: now,
+        expires_at,
+    };
+
+    HOLDS.with(|holds| holds.borrow_mut().insert(id, hold));
+    HOLDS_BY_ACCOUNT.with(|index| index.borrow_mut().insert((account, id), ()));
+    schedule_expiry(id, expires_at);
+
+    Ok(id)
+}
+
+/// Returns the held funds to the account's spendable balance.
+#[update]
+fn release_hold(hold_id: u64) -> Result<(), String> {
+    let hold = active_hold(hold_id)?;
+    remove_hold(&hold);
+    Ok(())
+}
+
+/// Transfers the held funds to `to`, returning the block index. No fee is
+/// charged; the held amount moves in full.
+#[update]
+fn capture_hold(hold_id: u64, to: Account) -> Result<Nat, String> {
+    let hold = active_hold(hold_id)?;
+    if freeze::is_frozen(&hold.account) {
+        return Err("Account is frozen".to_string());
+    }
+    remove_hold(&hold);
+
+    let tx = Transaction {
+        operation: Operation::Transfer {
+            from: hold.account,
+            to,
+            amount: hold.amount,
+            spender: Some(Account {
+                owner: hold.holder,
+                subaccount: None,
+            }),
+        },
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    apply_transaction(&tx).expect("held funds are always available");
+    Ok(record_transaction(tx))
+}
+
+#[query]
+fn get_hold(hold_id: u64) -> Option<Hold> {
+    HOLDS.with(|holds| holds.borrow().get(&hold_id))
+}
+
+#[query]
+fn get_holds(account: Account) -> Vec<Hold> {
+    holds_of(&account)
+}
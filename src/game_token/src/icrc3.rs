@@ -0,0 +1,191 @@
+use candid::Nat;
+
+pub use icrc_common::icrc3::{
+    tip_hash_tree, tip_root_hash, ArchiveInfo, DataCertificate, GetArchivesArgs, GetBlocksArgs,
+    GetBlocksResult, SupportedBlockType,
+};
+pub use icrc_common::Value;
+
+use crate::Account;
+
+// Ledger operations recorded in the block log
+#[derive(Clone)]
+pub enum Operation {
+    Mint {
+        to: Account,
+        amount: Nat,
+    },
+    Burn {
+        from: Account,
+        amount: Nat,
+        spender: Option<Account>,
+    },
+    Transfer {
+        from: Account,
+        to: Account,
+        amount: Nat,
+        spender: Option<Account>,
+    },
+    Approve {
+        from: Account,
+        spender: Account,
+        amount: Nat,
+        expected_allowance: Option<Nat>,
+        expires_at: Option<u64>,
+    },
+}
+
+impl Operation {
+    /// Accounts whose history includes this operation.
+    pub fn accounts(&self) -> Vec<&Account> {
+        match self {
+            Operation::Mint { to, .. } => vec![to],
+            Operation::Burn { from, spender, .. } => {
+                let mut accounts = vec![from];
+                accounts.extend(spender.as_ref());
+                accounts
+            }
+            Operation::Transfer {
+                from, to, spender, ..
+            } => {
+                let mut accounts = vec![from, to];
+                accounts.extend(spender.as_ref());
+                accounts
+            }
+            Operation::Approve { from, spender, .. } => vec![from, spender],
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Transaction {
+    pub operation: Operation,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+impl Transaction {
+    fn block_type(&self) -> &'static str {
+        match &self.operation {
+            Operation::Mint { .. } => "1mint",
+            Operation::Burn { .. } => "1burn",
+            Operation::Transfer { spender: None, .. } => "1xfer",
+            Operation::Transfer {
+                spender: Some(_), ..
+            } => "2xfer",
+            Operation::Approve { .. } => "2approve",
+        }
+    }
+
+    fn tx_value(&self) -> Value {
+        let mut tx = Vec::new();
+        match &self.operation {
+            Operation::Mint { to, amount } => {
+                tx.push(("to".to_string(), Value::from(to)));
+                tx.push(("amt".to_string(), Value::Nat(amount.clone())));
+            }
+            Operation::Burn {
+                from,
+                amount,
+                spender,
+            } => {
+                tx.push(("from".to_string(), Value::from(from)));
+                tx.push(("amt".to_string(), Value::Nat(amount.clone())));
+                if let Some(spender) = spender {
+                    tx.push(("spender".to_string(), Value::from(spender)));
+                }
+            }
+            Operation::Transfer {
+                from,
+                to,
+                amount,
+                spender,
+            } => {
+                tx.push(("from".to_string(), Value::from(from)));
+                tx.push(("to".to_string(), Value::from(to)));
+                tx.push(("amt".to_string(), Value::Nat(amount.clone())));
+                if let Some(spender) = spender {
+                    tx.push(("spender".to_string(), Value::from(spender)));
+                }
+            }
+            Operation::Approve {
+                from,
+                spender,
+                amount,
+                expected_allowance,
+                expires_at,
+            } => {
+                tx.push(("from".to_string(), Value::from(from)));
+                tx.push(("spender".to_string(), Value::from(spender)));
+                tx.push(("amt".to_string(), Value::Nat(amount.clone())));
+                if let Some(expected_allowance) = expected_allowance {
+                    tx.push((
+                        "expected_allowance".to_string(),
+                        Value::Nat(expected_allowance.clone()),
+                    ));
+                }
+                if let Some(expires_at) = expires_at {
+                    tx.push(("expires_at".to_string(), Value::Nat(Nat::from(*expires_at))));
+                }
+            }
+        }
+        if let Some(memo) = &self.memo {
+            tx.push(("memo".to_string(), Value::Blob(memo.clone())));
+        }
+        if let Some(created_at_time) = self.created_at_time {
+            tx.push(("ts".to_string(), Value::Nat(Nat::from(created_at_time))));
+        }
+        Value::Map(tx)
+    }
+
+    /// Hash identifying the transaction intent, used for deduplication.
+    pub fn hash(&self) -> [u8; 32] {
+        self.tx_value().hash()
+    }
+
+    /// Encodes the transaction as an ICRC-3 block chained to `phash`.
+    pub fn to_block(
+        &self,
+        phash: Option<[u8; 32]>,
+        timestamp: u64,
+        fee_collector: Option<&Account>,
+    ) -> Value {
+        let mut block = vec![
+            (
+                "btype".to_string(),
+                Value::Text(self.block_type().to_string()),
+            ),
+            ("ts".to_string(), Value::Nat(Nat::from(timestamp))),
+            ("tx".to_string(), self.tx_value()),
+        ];
+        if let Some(phash) = phash {
+            block.push(("phash".to_string(), Value::Blob(phash.to_vec())));
+        }
+        if let Some(fee) = &self.fee {
+            block.push(("fee".to_string(), Value::Nat(fee.clone())));
+        }
+        if let Some(fee_collector) = fee_collector {
+            block.push(("fee_col".to_string(), Value::from(fee_collector)));
+        }
+        Value::Map(block)
+    }
+}
+
+pub fn supported_block_types() -> Vec<SupportedBlockType> {
+    let icrc1 = "https://github.com/dfinity/ICRC-1/blob/main/standards/ICRC-1/README.md";
+    let icrc2 = "https://github.com/dfinity/ICRC-1/blob/main/standards/ICRC-2/README.md";
+    vec![
+        ("1mint", icrc1),
+        ("1burn", icrc1),
+        ("1xfer", icrc1),
+        ("2xfer", icrc2),
+        ("2approve", icrc2),
+    ]
+    .into_iter()
+    .map(|(block_type, url)| SupportedBlockType {
+        block_type: block_type.to_string(),
+        url: url.to_string(),
+    })
+    .collect()
+}
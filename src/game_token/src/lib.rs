@@ -1,19 +1,34 @@
-use candid::{CandidType, Deserialize, Nat, Principal};
+use candid::{CandidType, Deserialize, Int, Nat, Principal};
 use ic_cdk::api::management_canister::main::raw_rand;
-use ic_cdk::{caller, id, query, update};
+use ic_cdk::api::{data_certificate, set_certified_data, time};
+use ic_cdk::{caller, id, init, post_upgrade, query, update};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 use serde::Serialize;
 use std::cell::RefCell;
 
+mod batch_mint;
+mod consent;
+mod freeze;
+mod holds;
+mod icrc3;
+mod snapshots;
+mod staking;
+mod vesting;
+
+use icrc3::{
+    ArchiveInfo, DataCertificate, GetArchivesArgs, GetBlocksArgs, GetBlocksResult, Operation,
+    SupportedBlockType, Transaction, Value,
+};
+use icrc_common::block_log::{check_created_at_time, BlockLog, TimeError};
+use icrc_common::icrc10::{self, StandardRecord};
+use icrc_common::icrc21;
+use staking::StakingConfig;
+
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 // ICRC-1 Token types
-#[derive(CandidType, Deserialize, Clone)]
-pub struct Account {
-    pub owner: Principal,
-    pub subaccount: Option<[u8; 32]>,
-}
+pub use icrc_common::Account;
 
 #[derive(CandidType, Deserialize)]
 pub struct TransferArgs {
@@ -31,17 +46,265 @@ pub enum TransferError {
     BadBurn { min_burn_amount: Nat },
     InsufficientFunds { balance: Nat },
     TooOld,
-    CreatedInFuture,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+// ICRC-2 Approval types
+#[derive(CandidType, Deserialize)]
+pub struct ApproveArgs {
+    pub from_subaccount: Option<[u8; 32]>,
+    pub spender: Account,
+    pub amount: Nat,
+    pub expected_allowance: Option<Nat>,
+    pub expires_at: Option<u64>,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum ApproveError {
+    BadFee { expected_fee: Nat },
+    InsufficientFunds { balance: Nat },
+    AllowanceChanged { current_allowance: Nat },
+    Expired { ledger_time: u64 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct AllowanceArgs {
+    pub account: Account,
+    pub spender: Account,
+}
+
+#[derive(CandidType, Deserialize, Clone, Serialize)]
+pub struct Allowance {
+    pub allowance: Nat,
+    pub expires_at: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<[u8; 32]>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
     Duplicate { duplicate_of: Nat },
     TemporarilyUnavailable,
     GenericError { error_code: Nat, message: String },
 }
 
-// Token metadata
-const TOKEN_NAME: &str = "GameToken";
-const TOKEN_SYMBOL: &str = "GAME";
-const TOKEN_DECIMALS: u8 = 8;
-const TOKEN_FEE: u64 = 10_000; // 0.0001 tokens
+// Privileged roles; IC controllers implicitly hold `Controller`. Burns are
+// authorized by ownership or an ICRC-2 allowance, so `Burner` grants nothing
+// on its own and is kept so existing role records still decode
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Controller,
+    Minter,
+    Burner,
+}
+
+// Account transaction history types
+#[derive(CandidType, Deserialize)]
+pub struct GetAccountTransactionsArgs {
+    pub account: Account,
+    // Newest block index to return; defaults to the latest transaction
+    pub start: Option<Nat>,
+    pub max_results: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct AccountTransaction {
+    pub id: Nat,
+    pub block: Value,
+    pub balance_after: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct GetAccountTransactionsResult {
+    pub balance: Nat,
+    pub transactions: Vec<AccountTransaction>,
+    pub oldest_tx_id: Option<Nat>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct BalanceBreakdown {
+    pub total: Nat,
+    pub locked: Nat,
+    pub spendable: Nat,
+}
+
+// ICRC-1 metadata types
+#[derive(CandidType, Deserialize)]
+pub enum MetadataValue {
+    Nat(Nat),
+    Int(Int),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+// Init and upgrade arguments
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct FeatureFlags {
+    pub icrc2: bool,
+}
+
+// Reward pool refilled every epoch, drawn from by mint_game_tokens
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct EmissionSchedule {
+    pub epoch_length_seconds: u64,
+    pub epoch_budget: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct InitArgs {
+    pub token_name: String,
+    pub token_symbol: String,
+    pub decimals: Option<u8>,
+    pub transfer_fee: Nat,
+    // Defaults to this canister's default account
+    pub minting_account: Option<Account>,
+    pub fee_collector_account: Option<Account>,
+    pub initial_balances: Vec<(Account, Nat)>,
+    pub max_memo_length: Option<u16>,
+    pub feature_flags: Option<FeatureFlags>,
+    // Data URL or link shown by wallets
+    pub logo: Option<String>,
+    pub max_supply: Option<Nat>,
+    pub emission_schedule: Option<EmissionSchedule>,
+    pub staking: Option<StakingConfig>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum ChangeFeeCollector {
+    Unset,
+    SetTo(Account),
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct UpgradeArgs {
+    pub token_name: Option<String>,
+    pub token_symbol: Option<String>,
+    pub transfer_fee: Option<Nat>,
+    pub change_fee_collector: Option<ChangeFeeCollector>,
+    pub max_memo_length: Option<u16>,
+    pub feature_flags: Option<FeatureFlags>,
+    pub logo: Option<String>,
+    pub max_supply: Option<Nat>,
+    pub emission_schedule: Option<EmissionSchedule>,
+    pub staking: Option<StakingConfig>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum LedgerArg {
+    Init(InitArgs),
+    Upgrade(Option<UpgradeArgs>),
+}
+
+// Ledger configuration and scalar state, persisted in a stable cell
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct LedgerState {
+    pub token_name: String,
+    pub token_symbol: String,
+    pub decimals: u8,
+    pub transfer_fee: Nat,
+    pub minting_account: Option<Account>,
+    pub fee_collector_account: Option<Account>,
+    pub max_memo_length: u16,
+    pub feature_flags: FeatureFlags,
+    pub total_supply: Nat,
+    // Fields added after the first release must be optional, otherwise state
+    // written by an earlier version fails to decode in post_upgrade
+    pub logo: Option<String>,
+    pub max_supply: Option<Nat>,
+    pub emission_schedule: Option<EmissionSchedule>,
+    // Epoch the emission counter below belongs to
+    pub emission_epoch: Option<u64>,
+    pub emission_epoch_minted: Option<Nat>,
+    pub staking: Option<StakingConfig>,
+    pub next_stake_id: Option<u64>,
+    // Supply reserved by batch mints that have not finished yet
+    pub pending_batch_mint: Option<Nat>,
+    pub next_batch_mint_id: Option<u64>,
+    pub next_hold_id: Option<u64>,
+    pub next_snapshot_id: Option<u64>,
+    pub next_vesting_id: Option<u64>,
+}
+
+impl LedgerState {
+    pub fn pending_batch_mint(&self) -> Nat {
+        self.pending_batch_mint.clone().unwrap_or(Nat::from(0u64))
+    }
+}
+
+/// Hands out the next id of an id counter, which starts at zero.
+fn next_id(counter: &mut Option<u64>) -> u64 {
+    let id = counter.unwrap_or(0);
+    *counter = Some(id + 1);
+    id
+}
+
+impl Default for LedgerState {
+    fn default() -> Self {
+        Self {
+            token_name: String::new(),
+            token_symbol: String::new(),
+            decimals: DEFAULT_DECIMALS,
+            transfer_fee: Nat::from(0u64),
+            minting_account: None,
+            fee_collector_account: None,
+            max_memo_length: DEFAULT_MAX_MEMO_LENGTH,
+            feature_flags: FeatureFlags { icrc2: true },
+            total_supply: Nat::from(0u64),
+            logo: None,
+            max_supply: None,
+            emission_schedule: None,
+            emission_epoch: None,
+            emission_epoch_minted: None,
+            staking: None,
+            next_stake_id: None,
+            pending_batch_mint: None,
+            next_batch_mint_id: None,
+            next_hold_id: None,
+            next_snapshot_id: None,
+            next_vesting_id: None,
+        }
+    }
+}
+
+// Token metadata defaults
+const DEFAULT_DECIMALS: u8 = 8;
+const DEFAULT_MAX_MEMO_LENGTH: u16 = 32;
+
+// GenericError codes
+const ERROR_CODE_MEMO_TOO_LONG: u64 = 1;
+const ERROR_CODE_SELF_APPROVAL: u64 = 2;
+const ERROR_CODE_ICRC2_DISABLED: u64 = 3;
+const ERROR_CODE_SUPPLY_CAP_EXCEEDED: u64 = 4;
+const ERROR_CODE_ACCOUNT_FROZEN: u64 = 5;
+
+// Upper bound on transactions returned by a single get_account_transactions call
+const MAX_ACCOUNT_TRANSACTIONS_PER_RESPONSE: u64 = 100;
 
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -53,42 +316,263 @@ thread_local! {
         )
     );
 
-    static TOTAL_SUPPLY: RefCell<Nat> = RefCell::new(Nat::from(0u64));
+    // (owner, spender) -> allowance
+    static ALLOWANCES: RefCell<StableBTreeMap<(Account, Account), Allowance, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
+        )
+    );
+
+    // ICRC-3 block log (index memory, data memory) and the transaction
+    // hashes used for deduplication
+    static BLOCK_LOG: RefCell<BlockLog<Memory>> = RefCell::new(
+        BlockLog::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    // (role, principal) -> time the role was granted
+    static ROLES: RefCell<StableBTreeMap<(Role, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+        )
+    );
+
+    // (account, block index) -> account balance after that block
+    static ACCOUNT_TRANSACTIONS: RefCell<StableBTreeMap<(Account, u64), Nat, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        )
+    );
+
+    static STATE: RefCell<StableCell<LedgerState, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+            LedgerState::default(),
+        ).expect("failed to initialize the ledger state")
+    );
+
+    // Memory ids 9-10 are used by the staking module, 11 and 21 by batch
+    // minting, 12-13 by holds, 14-16 by account freezes, 17-18 by snapshots
+    // and 19-20 by vesting schedules
+}
+
+fn read_state<R>(f: impl FnOnce(&LedgerState) -> R) -> R {
+    STATE.with(|state| f(state.borrow().get()))
+}
+
+fn mutate_state<R>(f: impl FnOnce(&mut LedgerState) -> R) -> R {
+    STATE.with(|state| {
+        let mut cell = state.borrow_mut();
+        let mut ledger_state = cell.get().clone();
+        let result = f(&mut ledger_state);
+        cell.set(ledger_state)
+            .expect("failed to persist the ledger state");
+        result
+    })
+}
+
+// Canister lifecycle
+
+#[init]
+fn init(arg: LedgerArg) {
+    let LedgerArg::Init(args) = arg else {
+        ic_cdk::trap("Expected Init arguments on install");
+    };
+
+    mutate_state(|state| {
+        state.token_name = args.token_name;
+        state.token_symbol = args.token_symbol;
+        state.decimals = args.decimals.unwrap_or(DEFAULT_DECIMALS);
+        state.transfer_fee = args.transfer_fee;
+        state.minting_account = args.minting_account;
+        state.fee_collector_account = args.fee_collector_account;
+        state.max_memo_length = args.max_memo_length.unwrap_or(DEFAULT_MAX_MEMO_LENGTH);
+        if let Some(feature_flags) = args.feature_flags {
+            state.feature_flags = feature_flags;
+        }
+        state.logo = args.logo;
+        state.max_supply = args.max_supply;
+        state.emission_schedule = args.emission_schedule;
+        state.staking = args.staking;
+    });
+
+    for (to, amount) in args.initial_balances {
+        if let Err(message) = check_supply_cap(&amount) {
+            ic_cdk::trap(&message);
+        }
+
+        let tx = Transaction {
+            operation: Operation::Mint { to, amount },
+            fee: None,
+            memo: None,
+            created_at_time: None,
+        };
+        apply_transaction(&tx).expect("minting cannot fail");
+        record_transaction(tx);
+    }
+}
+
+// All state lives in stable memory, so no pre_upgrade hook is needed
+#[post_upgrade]
+fn post_upgrade(arg: Option<LedgerArg>) {
+    if let Some(LedgerArg::Upgrade(Some(args))) = arg {
+        mutate_state(|state| {
+            if let Some(token_name) = args.token_name {
+                state.token_name = token_name;
+            }
+            if let Some(token_symbol) = args.token_symbol {
+                state.token_symbol = token_symbol;
+            }
+            if let Some(transfer_fee) = args.transfer_fee {
+                state.transfer_fee = transfer_fee;
+            }
+            match args.change_fee_collector {
+                Some(ChangeFeeCollector::Unset) => state.fee_collector_account = None,
+                Some(ChangeFeeCollector::SetTo(account)) => {
+                    state.fee_collector_account = Some(account)
+                }
+                None => {}
+            }
+            if let Some(max_memo_length) = args.max_memo_length {
+                state.max_memo_length = max_memo_length;
+            }
+            if let Some(feature_flags) = args.feature_flags {
+                state.feature_flags = feature_flags;
+            }
+            if let Some(logo) = args.logo {
+                state.logo = Some(logo);
+            }
+            if let Some(max_supply) = args.max_supply {
+                if max_supply < state.total_supply {
+                    ic_cdk::trap("max_supply cannot be below the current total supply");
+                }
+                state.max_supply = Some(max_supply);
+            }
+            if let Some(emission_schedule) = args.emission_schedule {
+                state.emission_schedule = Some(emission_schedule);
+            }
+            if let Some(staking) = args.staking {
+                state.staking = Some(staking);
+            }
+        });
+    }
+
+    // Certified data does not survive upgrades
+    if let Some((index, block)) = last_block() {
+        set_certified_data(&icrc3::tip_root_hash(index, &block.hash()));
+    }
+    holds::schedule_expiries();
 }
 
 #[query]
 fn icrc1_name() -> String {
-    TOKEN_NAME.to_string()
+    read_state(|state| state.token_name.clone())
 }
 
 #[query]
 fn icrc1_symbol() -> String {
-    TOKEN_SYMBOL.to_string()
+    read_state(|state| state.token_symbol.clone())
 }
 
 #[query]
 fn icrc1_decimals() -> u8 {
-    TOKEN_DECIMALS
+    read_state(|state| state.decimals)
 }
 
 #[query]
 fn icrc1_fee() -> Nat {
-    Nat::from(TOKEN_FEE)
+    transfer_fee()
 }
 
 #[query]
 fn icrc1_total_supply() -> Nat {
-    TOTAL_SUPPLY.with(|supply| supply.borrow().clone())
+    read_state(|state| state.total_supply.clone())
 }
 
 #[query]
-fn icrc1_minting_account() -> Option<Account> {
-    Some(Account {
+fn icrc1_metadata() -> Vec<(String, MetadataValue)> {
+    read_state(|state| {
+        let mut metadata = vec![
+            (
+                "icrc1:name".to_string(),
+                MetadataValue::Text(state.token_name.clone()),
+            ),
+            (
+                "icrc1:symbol".to_string(),
+                MetadataValue::Text(state.token_symbol.clone()),
+            ),
+            (
+                "icrc1:decimals".to_string(),
+                MetadataValue::Nat(Nat::from(state.decimals)),
+            ),
+            (
+                "icrc1:fee".to_string(),
+                MetadataValue::Nat(state.transfer_fee.clone()),
+            ),
+            (
+                "icrc1:max_memo_length".to_string(),
+                MetadataValue::Nat(Nat::from(state.max_memo_length)),
+            ),
+        ];
+        if let Some(logo) = &state.logo {
+            metadata.push(("icrc1:logo".to_string(), MetadataValue::Text(logo.clone())));
+        }
+        metadata
+    })
+}
+
+#[query]
+fn icrc1_supported_standards() -> Vec<StandardRecord> {
+    let mut standards = vec![
+        (
+            "ICRC-1",
+            "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-1",
+        ),
+        (
+            "ICRC-3",
+            "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-3",
+        ),
+        ("ICRC-10", icrc10::ICRC10_URL),
+        ("ICRC-21", icrc21::ICRC21_URL),
+    ];
+    if icrc2_enabled() {
+        standards.insert(
+            1,
+            (
+                "ICRC-2",
+                "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-2",
+            ),
+        );
+    }
+
+    icrc10::standards(&standards)
+}
+
+#[query]
+fn icrc10_supported_standards() -> Vec<StandardRecord> {
+    icrc1_supported_standards()
+}
+
+fn transfer_fee() -> Nat {
+    read_state(|state| state.transfer_fee.clone())
+}
+
+fn minting_account() -> Account {
+    read_state(|state| state.minting_account.clone()).unwrap_or(Account {
         owner: id(),
         subaccount: None,
     })
 }
 
+#[query]
+fn icrc1_minting_account() -> Option<Account> {
+    Some(minting_account())
+}
+
 #[query]
 fn icrc1_balance_of(account: Account) -> Nat {
     BALANCES.with(|balances| {
@@ -96,6 +580,131 @@ fn icrc1_balance_of(account: Account) -> Nat {
     })
 }
 
+fn set_balance(account: Account, balance: Nat) {
+    snapshots::preserve_balance(&account);
+    BALANCES.with(|balances| {
+        let mut balances = balances.borrow_mut();
+        if balance == Nat::from(0u64) {
+            balances.remove(&account);
+        } else {
+            balances.insert(account, balance);
+        }
+    });
+}
+
+fn credit(account: &Account, amount: &Nat) {
+    let balance = icrc1_balance_of(account.clone());
+    set_balance(account.clone(), balance + amount.clone());
+}
+
+/// Part of the balance that cannot be spent, e.g. because it is staked, held
+/// in escrow or still vesting.
+fn locked_balance(account: &Account) -> Nat {
+    staking::staked_balance(account)
+        + holds::held_balance(account)
+        + vesting::vesting_balance(account)
+}
+
+fn spendable_balance(account: &Account) -> Nat {
+    let balance = icrc1_balance_of(account.clone());
+    let locked = locked_balance(account);
+    if balance > locked {
+        balance - locked
+    } else {
+        Nat::from(0u64)
+    }
+}
+
+#[query]
+fn get_balance_breakdown(account: Account) -> BalanceBreakdown {
+    BalanceBreakdown {
+        total: icrc1_balance_of(account.clone()),
+        locked: locked_balance(&account),
+        spendable: spendable_balance(&account),
+    }
+}
+
+/// Debits `amount` from `account`, returning the spendable balance if it is
+/// insufficient. Locked tokens are never debited.
+fn debit(account: &Account, amount: &Nat) -> Result<(), Nat> {
+    let spendable = spendable_balance(account);
+    if spendable < *amount {
+        return Err(spendable);
+    }
+    let balance = icrc1_balance_of(account.clone());
+    set_balance(account.clone(), balance - amount.clone());
+    Ok(())
+}
+
+fn increase_supply(amount: &Nat) {
+    mutate_state(|state| state.total_supply += amount.clone());
+}
+
+fn decrease_supply(amount: &Nat) {
+    mutate_state(|state| state.total_supply -= amount.clone());
+}
+
+/// Sends a charged fee to the fee collector, or burns it if none is set.
+fn collect_fee(fee: &Nat) {
+    match read_state(|state| state.fee_collector_account.clone()) {
+        Some(collector) => credit(&collector, fee),
+        None => decrease_supply(fee),
+    }
+}
+
+/// Applies the balance and supply changes of `tx`. Fails with the payer's
+/// balance, leaving state untouched, if it cannot cover the debit.
+fn apply_transaction(tx: &Transaction) -> Result<(), Nat> {
+    let fee = tx.fee.clone().unwrap_or(Nat::from(0u64));
+    match &tx.operation {
+        Operation::Mint { to, amount } => {
+            credit(to, amount);
+            increase_supply(amount);
+        }
+        Operation::Burn { from, amount, .. } => {
+            debit(from, amount)?;
+            decrease_supply(amount);
+        }
+        Operation::Transfer {
+            from, to, amount, ..
+        } => {
+            debit(from, &(amount.clone() + fee.clone()))?;
+            credit(to, amount);
+            collect_fee(&fee);
+        }
+        Operation::Approve { from, .. } => {
+            debit(from, &fee)?;
+            collect_fee(&fee);
+        }
+    }
+    Ok(())
+}
+
+fn memo_too_long(memo: &Option<Vec<u8>>) -> Option<String> {
+    memo_length_error(memo, read_state(|state| state.max_memo_length))
+}
+
+fn memo_length_error(memo: &Option<Vec<u8>>, max_memo_length: u16) -> Option<String> {
+    let max_memo_length = max_memo_length as usize;
+    match memo {
+        Some(memo) if memo.len() > max_memo_length => Some(format!(
+            "Memo is {} bytes, the maximum is {}",
+            memo.len(),
+            max_memo_length
+        )),
+        _ => None,
+    }
+}
+
+fn icrc2_enabled() -> bool {
+    read_state(|state| state.feature_flags.icrc2)
+}
+
+/// A provided fee must match the expected fee exactly.
+fn fee_matches(provided: &Option<Nat>, expected: &Nat) -> bool {
+    provided.as_ref().map_or(true, |fee| fee == expected)
+}
+
 #[update]
 fn icrc1_transfer(args: TransferArgs) -> Result<Nat, TransferError> {
     let caller = caller();
@@ -104,81 +713,761 @@ fn icrc1_transfer(args: TransferArgs) -> Result<Nat, TransferError> {
         subaccount: args.from_subaccount,
     };
 
-    // Check balance
-    let balance = icrc1_balance_of(from_account.clone());
-    let amount_with_fee = args.amount.clone() + Nat::from(TOKEN_FEE);
-    
-    if balance < amount_with_fee {
-        return Err(TransferError::InsufficientFunds { balance });
+    if freeze::is_frozen(&from_account) {
+        return Err(TransferError::GenericError {
+            error_code: Nat::from(ERROR_CODE_ACCOUNT_FROZEN),
+            message: "Account is frozen".to_string(),
+        });
     }
 
-    // Perform transfer
-    BALANCES.with(|balances| {
-        let mut balances = balances.borrow_mut();
-        
-        // Deduct from sender
-        let new_from_balance = balance - amount_with_fee;
-        if new_from_balance == Nat::from(0u64) {
-            balances.remove(&from_account);
+    if let Some(message) = memo_too_long(&args.memo) {
+        return Err(TransferError::GenericError {
+            error_code: Nat::from(ERROR_CODE_MEMO_TOO_LONG),
+            message,
+        });
+    }
+
+    // Transfers from the minting account mint, transfers to it burn; neither pays a fee
+    let minting_account = minting_account();
+    let fee = transfer_fee();
+    let operation = if from_account == minting_account || args.to == minting_account {
+        if !fee_matches(&args.fee, &Nat::from(0u64)) {
+            return Err(TransferError::BadFee {
+                expected_fee: Nat::from(0u64),
+            });
+        }
+
+        if from_account == minting_account {
+            if let Err(message) = check_supply_cap(&args.amount) {
+                return Err(TransferError::GenericError {
+                    error_code: Nat::from(ERROR_CODE_SUPPLY_CAP_EXCEEDED),
+                    message,
+                });
+            }
+            Operation::Mint {
+                to: args.to,
+                amount: args.amount,
+            }
+        } else {
+            if args.amount < fee {
+                return Err(TransferError::BadBurn {
+                    min_burn_amount: fee,
+                });
+            }
+            Operation::Burn {
+                from: from_account,
+                amount: args.amount,
+                spender: None,
+            }
+        }
+    } else {
+        if !fee_matches(&args.fee, &fee) {
+            return Err(TransferError::BadFee { expected_fee: fee });
+        }
+
+        Operation::Transfer {
+            from: from_account,
+            to: args.to,
+            amount: args.amount,
+            spender: None,
+        }
+    };
+
+    let charged_fee = matches!(operation, Operation::Transfer { .. }).then_some(fee);
+    let tx = Transaction {
+        operation,
+        fee: charged_fee,
+        memo: args.memo,
+        created_at_time: args.created_at_time,
+    };
+    deduplicate(&tx)?;
+
+    apply_transaction(&tx).map_err(|balance| TransferError::InsufficientFunds { balance })?;
+
+    Ok(record_transaction(tx))
+}
+
+// Transaction deduplication
+
+enum DedupError {
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+}
+
+impl From<DedupError> for TransferError {
+    fn from(err: DedupError) -> Self {
+        match err {
+            DedupError::TooOld => TransferError::TooOld,
+            DedupError::CreatedInFuture { ledger_time } => {
+                TransferError::CreatedInFuture { ledger_time }
+            }
+            DedupError::Duplicate { duplicate_of } => TransferError::Duplicate { duplicate_of },
+        }
+    }
+}
+
+impl From<DedupError> for ApproveError {
+    fn from(err: DedupError) -> Self {
+        match err {
+            DedupError::TooOld => ApproveError::TooOld,
+            DedupError::CreatedInFuture { ledger_time } => {
+                ApproveError::CreatedInFuture { ledger_time }
+            }
+            DedupError::Duplicate { duplicate_of } => ApproveError::Duplicate { duplicate_of },
+        }
+    }
+}
+
+impl From<DedupError> for TransferFromError {
+    fn from(err: DedupError) -> Self {
+        match err {
+            DedupError::TooOld => TransferFromError::TooOld,
+            DedupError::CreatedInFuture { ledger_time } => {
+                TransferFromError::CreatedInFuture { ledger_time }
+            }
+            DedupError::Duplicate { duplicate_of } => TransferFromError::Duplicate { duplicate_of },
+        }
+    }
+}
+
+/// Rejects transactions outside the deduplication window and replays of
+/// transactions already recorded within it. Transactions without
+/// `created_at_time` are never deduplicated.
+fn deduplicate(tx: &Transaction) -> Result<(), DedupError> {
+    let Some(created_at_time) = tx.created_at_time else {
+        return Ok(());
+    };
+
+    let now = time();
+    check_created_at_time(Some(created_at_time), now).map_err(|err| match err {
+        TimeError::TooOld => DedupError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => DedupError::CreatedInFuture { ledger_time },
+    })?;
+
+    match BLOCK_LOG.with(|log| log.borrow_mut().find_duplicate(&tx.hash(), now)) {
+        Some(duplicate_of) => Err(DedupError::Duplicate {
+            duplicate_of: Nat::from(duplicate_of),
+        }),
+        None => Ok(()),
+    }
+}
+
+// ICRC-2 Standard Functions
+
+/// Returns the allowance `spender` holds over `account`, treating expired
+/// approvals as zero.
+fn current_allowance(account: &Account, spender: &Account) -> Allowance {
+    let allowance =
+        ALLOWANCES.with(|allowances| allowances.borrow().get(&(account.clone(), spender.clone())));
+
+    match allowance {
+        Some(allowance) if allowance.expires_at.map_or(true, |expiry| expiry > time()) => allowance,
+        _ => Allowance {
+            allowance: Nat::from(0u64),
+            expires_at: None,
+        },
+    }
+}
+
+fn set_allowance(account: Account, spender: Account, allowance: Allowance) {
+    ALLOWANCES.with(|allowances| {
+        let mut allowances = allowances.borrow_mut();
+        if allowance.allowance == Nat::from(0u64) {
+            allowances.remove(&(account, spender));
         } else {
-            balances.insert(from_account, new_from_balance);
+            allowances.insert((account, spender), allowance);
         }
-        
-        // Add to recipient
-        let to_balance = balances.get(&args.to).unwrap_or(Nat::from(0u64));
-        balances.insert(args.to, to_balance + args.amount);
     });
+}
 
-    // Return transaction ID (simplified)
-    Ok(Nat::from(ic_cdk::api::time()))
+#[query]
+fn icrc2_allowance(args: AllowanceArgs) -> Allowance {
+    current_allowance(&args.account, &args.spender)
 }
 
-// Game-specific functions
 #[update]
-async fn mint_game_tokens(to: Account, amount: Nat) -> Result<(), String> {
+fn icrc2_approve(args: ApproveArgs) -> Result<Nat, ApproveError> {
+    if !icrc2_enabled() {
+        return Err(ApproveError::GenericError {
+            error_code: Nat::from(ERROR_CODE_ICRC2_DISABLED),
+            message: "ICRC-2 features are not enabled on this ledger".to_string(),
+        });
+    }
+
     let caller = caller();
-    
-    // Only the game engine can mint tokens
-    if caller != id() {
-        return Err("Unauthorized".to_string());
+    let from_account = Account {
+        owner: caller,
+        subaccount: args.from_subaccount,
+    };
+
+    if freeze::is_frozen(&from_account) {
+        return Err(ApproveError::GenericError {
+            error_code: Nat::from(ERROR_CODE_ACCOUNT_FROZEN),
+            message: "Account is frozen".to_string(),
+        });
     }
 
-    BALANCES.with(|balances| {
-        let mut balances = balances.borrow_mut();
-        let current_balance = balances.get(&to).unwrap_or(Nat::from(0u64));
-        balances.insert(to, current_balance + amount.clone());
-    });
+    if from_account.owner == args.spender.owner {
+        return Err(ApproveError::GenericError {
+            error_code: Nat::from(ERROR_CODE_SELF_APPROVAL),
+            message: "Self approval is not allowed".to_string(),
+        });
+    }
 
-    TOTAL_SUPPLY.with(|supply| {
-        let mut supply = supply.borrow_mut();
-        *supply = supply.clone() + amount;
-    });
+    if let Some(message) = memo_too_long(&args.memo) {
+        return Err(ApproveError::GenericError {
+            error_code: Nat::from(ERROR_CODE_MEMO_TOO_LONG),
+            message,
+        });
+    }
 
-    Ok(())
+    let fee = transfer_fee();
+    if !fee_matches(&args.fee, &fee) {
+        return Err(ApproveError::BadFee { expected_fee: fee });
+    }
+
+    let now = time();
+    if let Some(expires_at) = args.expires_at {
+        if expires_at <= now {
+            return Err(ApproveError::Expired { ledger_time: now });
+        }
+    }
+
+    let tx = Transaction {
+        operation: Operation::Approve {
+            from: from_account.clone(),
+            spender: args.spender.clone(),
+            amount: args.amount.clone(),
+            expected_allowance: args.expected_allowance.clone(),
+            expires_at: args.expires_at,
+        },
+        fee: Some(fee),
+        memo: args.memo,
+        created_at_time: args.created_at_time,
+    };
+    deduplicate(&tx)?;
+
+    let current = current_allowance(&from_account, &args.spender);
+    if let Some(expected_allowance) = &args.expected_allowance {
+        if *expected_allowance != current.allowance {
+            return Err(ApproveError::AllowanceChanged {
+                current_allowance: current.allowance,
+            });
+        }
+    }
+
+    // Approvals are paid for by the approver
+    apply_transaction(&tx).map_err(|balance| ApproveError::InsufficientFunds { balance })?;
+    set_allowance(
+        from_account,
+        args.spender,
+        Allowance {
+            allowance: args.amount,
+            expires_at: args.expires_at,
+        },
+    );
+
+    Ok(record_transaction(tx))
 }
 
 #[update]
-async fn burn_game_tokens(from: Account, amount: Nat) -> Result<(), String> {
-    let balance = icrc1_balance_of(from.clone());
-    
-    if balance < amount {
-        return Err("Insufficient balance".to_string());
+fn icrc2_transfer_from(args: TransferFromArgs) -> Result<Nat, TransferFromError> {
+    if !icrc2_enabled() {
+        return Err(TransferFromError::GenericError {
+            error_code: Nat::from(ERROR_CODE_ICRC2_DISABLED),
+            message: "ICRC-2 features are not enabled on this ledger".to_string(),
+        });
     }
 
-    BALANCES.with(|balances| {
-        let mut balances = balances.borrow_mut();
-        let new_balance = balance - amount.clone();
-        if new_balance == Nat::from(0u64) {
-            balances.remove(&from);
+    let caller = caller();
+    let spender = Account {
+        owner: caller,
+        subaccount: args.spender_subaccount,
+    };
+
+    if freeze::is_frozen(&args.from) {
+        return Err(TransferFromError::GenericError {
+            error_code: Nat::from(ERROR_CODE_ACCOUNT_FROZEN),
+            message: "Account is frozen".to_string(),
+        });
+    }
+
+    if let Some(message) = memo_too_long(&args.memo) {
+        return Err(TransferFromError::GenericError {
+            error_code: Nat::from(ERROR_CODE_MEMO_TOO_LONG),
+            message,
+        });
+    }
+
+    // Transfers to the minting account burn and pay no fee
+    let is_burn = args.to == minting_account();
+    let transfer_fee = transfer_fee();
+    let fee = if is_burn {
+        Nat::from(0u64)
+    } else {
+        transfer_fee.clone()
+    };
+    if !fee_matches(&args.fee, &fee) {
+        return Err(TransferFromError::BadFee { expected_fee: fee });
+    }
+    if is_burn && args.amount < transfer_fee {
+        return Err(TransferFromError::BadBurn {
+            min_burn_amount: transfer_fee,
+        });
+    }
+
+    let amount_with_fee = args.amount.clone() + fee.clone();
+
+    // An account spending its own funds does not need an allowance
+    let allowance = if spender == args.from {
+        None
+    } else {
+        let allowance = current_allowance(&args.from, &spender);
+        if allowance.allowance < amount_with_fee {
+            return Err(TransferFromError::InsufficientAllowance {
+                allowance: allowance.allowance,
+            });
+        }
+        Some(allowance)
+    };
+
+    let tx_spender = allowance.is_some().then(|| spender.clone());
+    let operation = if is_burn {
+        Operation::Burn {
+            from: args.from.clone(),
+            amount: args.amount,
+            spender: tx_spender,
+        }
+    } else {
+        Operation::Transfer {
+            from: args.from.clone(),
+            to: args.to,
+            amount: args.amount,
+            spender: tx_spender,
+        }
+    };
+    let tx = Transaction {
+        operation,
+        fee: (!is_burn).then_some(fee),
+        memo: args.memo,
+        created_at_time: args.created_at_time,
+    };
+    deduplicate(&tx)?;
+
+    apply_transaction(&tx).map_err(|balance| TransferFromError::InsufficientFunds { balance })?;
+
+    if let Some(allowance) = allowance {
+        set_allowance(
+            args.from,
+            spender,
+            Allowance {
+                allowance: allowance.allowance - amount_with_fee,
+                expires_at: allowance.expires_at,
+            },
+        );
+    }
+
+    Ok(record_transaction(tx))
+}
+
+// ICRC-3 Standard Functions
+
+fn last_block() -> Option<(u64, Value)> {
+    BLOCK_LOG.with(|log| log.borrow().last_block())
+}
+
+/// Appends the transaction to the block log, re-certifies the chain tip and
+/// returns the new block index.
+fn record_transaction(tx: Transaction) -> Nat {
+    let phash = last_block().map(|(_, block)| block.hash());
+    let fee_collector = tx
+        .fee
+        .as_ref()
+        .and_then(|_| read_state(|state| state.fee_collector_account.clone()));
+    let block = tx.to_block(phash, time(), fee_collector.as_ref());
+    let block_hash = block.hash();
+    let dedup_key = tx
+        .created_at_time
+        .map(|created_at_time| (created_at_time, tx.hash()));
+    let index = BLOCK_LOG.with(|log| log.borrow_mut().append(&block, dedup_key));
+
+    // Index the block for every account it touches; balances are already updated
+    let mut accounts = tx.operation.accounts();
+    accounts.extend(fee_collector.as_ref());
+    for account in accounts {
+        let balance_after = icrc1_balance_of(account.clone());
+        ACCOUNT_TRANSACTIONS.with(|index_map| {
+            index_map
+                .borrow_mut()
+                .insert((account.clone(), index), balance_after)
+        });
+    }
+
+    set_certified_data(&icrc3::tip_root_hash(index, &block_hash));
+    Nat::from(index)
+}
+
+#[query]
+fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    BLOCK_LOG.with(|log| log.borrow().get_blocks(args))
+}
+
+#[query]
+fn icrc3_get_archives(_args: GetArchivesArgs) -> Vec<ArchiveInfo> {
+    // All blocks are kept in this canister's stable memory
+    Vec::new()
+}
+
+#[query]
+fn icrc3_get_tip_certificate() -> Option<DataCertificate> {
+    let certificate = data_certificate()?;
+    let (index, block) = last_block()?;
+
+    Some(DataCertificate {
+        certificate,
+        hash_tree: icrc3::tip_hash_tree(index, &block.hash()),
+    })
+}
+
+#[query]
+fn icrc3_supported_block_types() -> Vec<SupportedBlockType> {
+    icrc3::supported_block_types()
+}
+
+// Emission schedule and supply cap
+
+#[derive(CandidType, Deserialize)]
+pub struct EpochStatus {
+    pub epoch: u64,
+    pub start: u64,
+    pub end: u64,
+    pub budget: Nat,
+    pub minted: Nat,
+    pub remaining: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct EmissionStatus {
+    pub total_supply: Nat,
+    pub max_supply: Option<Nat>,
+    pub remaining_supply: Option<Nat>,
+    pub current_epoch: Option<EpochStatus>,
+}
+
+/// Room left under the max supply, net of supply reserved by pending batch
+/// mints. `None` when the supply is uncapped.
+fn remaining_supply(state: &LedgerState) -> Option<Nat> {
+    state.max_supply.as_ref().map(|max_supply| {
+        let committed = state.total_supply.clone() + state.pending_batch_mint();
+        if committed < *max_supply {
+            max_supply.clone() - committed
         } else {
-            balances.insert(from, new_balance);
+            Nat::from(0u64)
         }
+    })
+}
+
+fn check_supply_cap(amount: &Nat) -> Result<(), String> {
+    read_state(|state| fits_supply_cap(state, amount))
+}
+
+fn fits_supply_cap(state: &LedgerState, amount: &Nat) -> Result<(), String> {
+    match (&state.max_supply, remaining_supply(state)) {
+        (Some(max_supply), Some(remaining)) if *amount > remaining => Err(format!(
+            "Minting {} would exceed the max supply of {}",
+            amount, max_supply
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Epoch index at `now` and the amount already minted in it, treating a
+/// stale counter from a previous epoch as zero.
+fn current_epoch(state: &LedgerState, schedule: &EmissionSchedule, now: u64) -> (u64, Nat) {
+    let epoch_length_nanos = schedule
+        .epoch_length_seconds
+        .max(1)
+        .saturating_mul(1_000_000_000);
+    let epoch = now / epoch_length_nanos;
+    match (state.emission_epoch, &state.emission_epoch_minted) {
+        (Some(counted_epoch), Some(minted)) if counted_epoch == epoch => (epoch, minted.clone()),
+        _ => (epoch, Nat::from(0u64)),
+    }
+}
+
+/// Part of the current epoch's reward budget not minted yet. `None` when no
+/// emission schedule is set.
+fn remaining_emission_budget(state: &LedgerState) -> Option<Nat> {
+    state.emission_schedule.as_ref().map(|schedule| {
+        let (_, minted) = current_epoch(state, schedule, time());
+        if minted < schedule.epoch_budget {
+            schedule.epoch_budget.clone() - minted
+        } else {
+            Nat::from(0u64)
+        }
+    })
+}
+
+/// Charges `amount` against the current epoch's reward budget.
+fn draw_emission_budget(amount: &Nat) -> Result<(), String> {
+    mutate_state(|state| charge_emission_budget(state, amount, time()))
+}
+
+fn charge_emission_budget(state: &mut LedgerState, amount: &Nat, now: u64) -> Result<(), String> {
+    let Some(schedule) = state.emission_schedule.clone() else {
+        return Ok(());
+    };
+
+    let (epoch, minted) = current_epoch(state, &schedule, now);
+    let minted = minted + amount.clone();
+    if minted > schedule.epoch_budget {
+        return Err(format!("Emission budget for epoch {} is exhausted", epoch));
+    }
+
+    state.emission_epoch = Some(epoch);
+    state.emission_epoch_minted = Some(minted);
+    Ok(())
+}
+
+#[update]
+fn set_emission_schedule(emission_schedule: Option<EmissionSchedule>) -> Result<(), String> {
+    require_role(Role::Controller)?;
+
+    mutate_state(|state| state.emission_schedule = emission_schedule);
+    Ok(())
+}
+
+#[query]
+fn get_emission_status() -> EmissionStatus {
+    read_state(|state| {
+        let current_epoch = state.emission_schedule.as_ref().map(|schedule| {
+            let (epoch, minted) = current_epoch(state, schedule, time());
+            let epoch_length_nanos = schedule
+                .epoch_length_seconds
+                .max(1)
+                .saturating_mul(1_000_000_000);
+            let remaining = if minted < schedule.epoch_budget {
+                schedule.epoch_budget.clone() - minted.clone()
+            } else {
+                Nat::from(0u64)
+            };
+
+            EpochStatus {
+                epoch,
+                start: epoch * epoch_length_nanos,
+                end: (epoch + 1) * epoch_length_nanos,
+                budget: schedule.epoch_budget.clone(),
+                minted,
+                remaining,
+            }
+        });
+
+        EmissionStatus {
+            total_supply: state.total_supply.clone(),
+            max_supply: state.max_supply.clone(),
+            remaining_supply: remaining_supply(state),
+            current_epoch,
+        }
+    })
+}
+
+// Account transaction history
+
+/// Returns a newest-first page of the blocks touching `account`, each with the
+/// account's balance right after it.
+#[query]
+fn get_account_transactions(args: GetAccountTransactionsArgs) -> GetAccountTransactionsResult {
+    let start = args
+        .start
+        .map(|start| u64::try_from(start.0).unwrap_or(u64::MAX))
+        .unwrap_or(u64::MAX);
+    let max_results = u64::try_from(args.max_results.0)
+        .unwrap_or(u64::MAX)
+        .min(MAX_ACCOUNT_TRANSACTIONS_PER_RESPONSE);
+
+    let (page, oldest_tx_id) = ACCOUNT_TRANSACTIONS.with(|index_map| {
+        let index_map = index_map.borrow();
+        let page: Vec<(u64, Nat)> = index_map
+            .range((args.account.clone(), 0)..=(args.account.clone(), start))
+            .rev()
+            .take(max_results as usize)
+            .map(|((_, block_index), balance_after)| (block_index, balance_after))
+            .collect();
+        let oldest_tx_id = index_map
+            .range((args.account.clone(), 0)..=(args.account.clone(), u64::MAX))
+            .next()
+            .map(|((_, block_index), _)| Nat::from(block_index));
+        (page, oldest_tx_id)
+    });
+
+    let transactions = BLOCK_LOG.with(|log| {
+        let log = log.borrow();
+        page.into_iter()
+            .filter_map(|(block_index, balance_after)| {
+                log.get(block_index).map(|block| AccountTransaction {
+                    id: Nat::from(block_index),
+                    block,
+                    balance_after,
+                })
+            })
+            .collect()
+    });
+
+    GetAccountTransactionsResult {
+        balance: icrc1_balance_of(args.account),
+        transactions,
+        oldest_tx_id,
+    }
+}
+
+// Role management
+
+fn has_role(principal: &Principal, role: Role) -> bool {
+    if role == Role::Controller && ic_cdk::api::is_controller(principal) {
+        return true;
+    }
+    ROLES.with(|roles| roles.borrow().contains_key(&(role, *principal)))
+}
+
+fn require_role(role: Role) -> Result<(), String> {
+    if has_role(&caller(), role) {
+        Ok(())
+    } else {
+        Err("Unauthorized".to_string())
+    }
+}
+
+#[update]
+fn grant_role(principal: Principal, role: Role) -> Result<(), String> {
+    require_role(Role::Controller)?;
+
+    ROLES.with(|roles| {
+        roles.borrow_mut().insert((role, principal), time());
     });
+    Ok(())
+}
+
+#[update]
+fn revoke_role(principal: Principal, role: Role) -> Result<(), String> {
+    require_role(Role::Controller)?;
 
-    TOTAL_SUPPLY.with(|supply| {
-        let mut supply = supply.borrow_mut();
-        *supply = supply.clone() - amount;
+    ROLES.with(|roles| {
+        roles.borrow_mut().remove(&(role, principal));
     });
+    Ok(())
+}
+
+#[query]
+fn get_role_members(role: Role) -> Vec<Principal> {
+    ROLES.with(|roles| {
+        roles
+            .borrow()
+            .range((role, Principal::from_slice(&[]))..)
+            .take_while(|((member_role, _), _)| *member_role == role)
+            .map(|((_, principal), _)| principal)
+            .collect()
+    })
+}
+
+#[query]
+fn get_roles(principal: Principal) -> Vec<Role> {
+    [Role::Controller, Role::Minter, Role::Burner]
+        .into_iter()
+        .filter(|role| has_role(&principal, *role))
+        .collect()
+}
+
+// Game-specific functions
+#[update]
+async fn mint_game_tokens(to: Account, amount: Nat) -> Result<Nat, String> {
+    // Only registered minters (e.g. the game engine) can mint tokens
+    require_role(Role::Minter)?;
+    check_supply_cap(&amount)?;
+    draw_emission_budget(&amount)?;
+
+    let tx = Transaction {
+        operation: Operation::Mint { to, amount },
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    apply_transaction(&tx).expect("minting cannot fail");
+
+    Ok(record_transaction(tx))
+}
 
+/// Burns from `from`. The owner may always burn its own tokens; anyone else
+/// needs an ICRC-2 allowance covering `amount`.
+#[update]
+async fn burn_game_tokens(from: Account, amount: Nat) -> Result<Nat, String> {
+    let caller = caller();
+
+    if freeze::is_frozen(&from) {
+        return Err("Account is frozen".to_string());
+    }
+
+    let allowance = if from.owner == caller {
+        None
+    } else {
+        let spender = Account {
+            owner: caller,
+            subaccount: None,
+        };
+        let allowance = current_allowance(&from, &spender);
+        if allowance.allowance < amount {
+            return Err("Insufficient allowance".to_string());
+        }
+        Some((spender, allowance))
+    };
+
+    let tx = Transaction {
+        operation: Operation::Burn {
+            from: from.clone(),
+            amount: amount.clone(),
+            spender: allowance.as_ref().map(|(spender, _)| spender.clone()),
+        },
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    apply_transaction(&tx).map_err(|_| "Insufficient balance".to_string())?;
+
+    if let Some((spender, allowance)) = allowance {
+        set_allowance(
+            from,
+            spender,
+            Allowance {
+                allowance: allowance.allowance - amount,
+                expires_at: allowance.expires_at,
+            },
+        );
+    }
+
+    Ok(record_transaction(tx))
+}
+
+// Configuration
+#[update]
+fn set_fee_collector(fee_collector: Option<Account>) -> Result<(), String> {
+    require_role(Role::Controller)?;
+
+    mutate_state(|state| state.fee_collector_account = fee_collector);
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[query]
+fn get_fee_collector() -> Option<Account> {
+    read_state(|state| state.fee_collector_account.clone())
+}
+
+// Textual ICRC-1 accounts
+#[query]
+fn account_to_text(account: Account) -> String {
+    account.to_string()
+}
+
+#[query]
+fn account_from_text(text: String) -> Result<Account, String> {
+    text.parse::<Account>().map_err(|err| err.to_string())
+}
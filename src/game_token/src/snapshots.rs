@@ -0,0 +1,192 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::ops::Bound;
+
+use crate::{
+    icrc1_balance_of, last_block, mutate_state, next_id, read_state, require_role, Account, Memory,
+    Role, BALANCES, MEMORY_MANAGER,
+};
+
+// Upper bound on holders returned by a single snapshot_holders call
+const MAX_SNAPSHOT_HOLDERS_PER_RESPONSE: usize = 500;
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct SnapshotInfo {
+    pub id: u64,
+    pub taken_at: u64,
+    pub taken_by: Principal,
+    pub total_supply: Nat,
+    // Index of the newest block included in the snapshot
+    pub last_block_index: Option<Nat>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct SnapshotHoldersResult {
+    pub holders: Vec<(Account, Nat)>,
+    // Pass back as `cursor` to fetch the next page; unset on the last page
+    pub next_cursor: Option<Account>,
+}
+
+thread_local! {
+    static SNAPSHOTS: RefCell<StableBTreeMap<u64, SnapshotInfo, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))),
+        )
+    );
+
+    // (snapshot id, account) -> balance when the snapshot was taken, written
+    // the first time the account's balance changes after that snapshot
+    static SNAPSHOT_BALANCES: RefCell<StableBTreeMap<(u64, Account), Nat, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18))),
+        )
+    );
+}
+
+fn latest_snapshot() -> Option<u64> {
+    read_state(|state| state.next_snapshot_id?.checked_sub(1))
+}
+
+/// Saves the current balance of `account` into the newest snapshot before it
+/// is overwritten, unless the snapshot already holds a copy.
+pub fn preserve_balance(account: &Account) {
+    let Some(snapshot_id) = latest_snapshot() else {
+        return;
+    };
+    let key = (snapshot_id, account.clone());
+    if SNAPSHOT_BALANCES.with(|balances| balances.borrow().contains_key(&key)) {
+        return;
+    }
+
+    let balance = icrc1_balance_of(account.clone());
+    SNAPSHOT_BALANCES.with(|balances| balances.borrow_mut().insert(key, balance));
+}
+
+/// Balance of `account` at `snapshot_id`: the first copy saved by this or a
+/// later snapshot, or the live balance if it has not changed since.
+fn snapshot_balance(snapshot_id: u64, latest: u64, account: &Account) -> Nat {
+    SNAPSHOT_BALANCES
+        .with(|balances| {
+            let balances = balances.borrow();
+            (snapshot_id..=latest).find_map(|id| balances.get(&(id, account.clone())))
+        })
+        .unwrap_or_else(|| icrc1_balance_of(account.clone()))
+}
+
+fn snapshot_exists(snapshot_id: u64) -> bool {
+    SNAPSHOTS.with(|snapshots| snapshots.borrow().contains_key(&snapshot_id))
+}
+
+/// Records a point-in-time view of all balances. Balances are copied lazily,
+/// so taking a snapshot is constant-time.
+#[update]
+fn take_snapshot() -> Result<u64, String> {
+    require_role(Role::Controller)?;
+
+    let (id, total_supply) = mutate_state(|state| {
+        let id = next_id(&mut state.next_snapshot_id);
+        (id, state.total_supply.clone())
+    });
+    let info = SnapshotInfo {
+        id,
+        taken_at: time(),
+        taken_by: caller(),
+        total_supply,
+        last_block_index: last_block().map(|(index, _)| Nat::from(index)),
+    };
+    SNAPSHOTS.with(|snapshots| snapshots.borrow_mut().insert(id, info));
+
+    Ok(id)
+}
+
+#[query]
+fn get_snapshot(snapshot_id: u64) -> Option<SnapshotInfo> {
+    SNAPSHOTS.with(|snapshots| snapshots.borrow().get(&snapshot_id))
+}
+
+#[query]
+fn balance_at(snapshot_id: u64, account: Account) -> Result<Nat, String> {
+    let latest = latest_snapshot().ok_or("Snapshot not found")?;
+    if !snapshot_exists(snapshot_id) {
+        return Err("Snapshot not found".to_string());
+    }
+    Ok(snapshot_balance(snapshot_id, latest, &account))
+}
+
+/// Returns a page of accounts with a non-zero balance at `snapshot_id`, in
+/// account order, starting after `cursor`.
+#[query]
+fn snapshot_holders(
+    snapshot_id: u64,
+    cursor: Option<Account>,
+) -> Result<SnapshotHoldersResult, String> {
+    let latest = latest_snapshot().ok_or("Snapshot not found")?;
+    if !snapshot_exists(snapshot_id) {
+        return Err("Snapshot not found".to_string());
+    }
+
+    let limit = MAX_SNAPSHOT_HOLDERS_PER_RESPONSE;
+    let after = |account: &Account| cursor.as_ref().map_or(true, |cursor| account > cursor);
+
+    // Holders are the live accounts plus those whose balance was copied by
+    // this or a later snapshot before dropping to zero. Taking the first
+    // `limit` accounts past the cursor from each source is enough to find the
+    // first `limit` accounts of their union.
+    let mut candidates: Vec<Account> = BALANCES.with(|balances| {
+        let balances = balances.borrow();
+        let start = match &cursor {
+            Some(cursor) => Bound::Excluded(cursor.clone()),
+            None => Bound::Unbounded,
+        };
+        balances
+            .range((start, Bound::Unbounded))
+            .map(|(account, _)| account)
+            .take(limit)
+            .collect()
+    });
+    SNAPSHOT_BALANCES.with(|balances| {
+        let balances = balances.borrow();
+        let min_account = Account {
+            owner: Principal::from_slice(&[]),
+            subaccount: None,
+        };
+        for id in snapshot_id..=latest {
+            let start = cursor.clone().unwrap_or_else(|| min_account.clone());
+            candidates.extend(
+                balances
+                    .range((id, start)..)
+                    .take_while(|((entry_id, _), _)| *entry_id == id)
+                    .map(|((_, account), _)| account)
+                    .filter(|account| after(account))
+                    .take(limit),
+            );
+        }
+    });
+    candidates.sort();
+    candidates.dedup();
+    candidates.truncate(limit);
+
+    let next_cursor = if candidates.len() == limit {
+        candidates.last().cloned()
+    } else {
+        None
+    };
+    let holders = candidates
+        .into_iter()
+        .map(|account| {
+            let balance = snapshot_balance(snapshot_id, latest, &account);
+            (account, balance)
+        })
+        .filter(|(_, balance)| *balance > Nat::from(0u64))
+        .collect();
+
+    Ok(SnapshotHoldersResult {
+        holders,
+        next_cursor,
+    })
+}
@@ -0,0 +1,236 @@
+use candid::{CandidType, Deserialize, Nat};
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use serde::Serialize;
+use std::cell::RefCell;
+
+use crate::icrc3::{Operation, Transaction};
+use crate::{
+    apply_transaction, draw_emission_budget, freeze, mutate_state, next_id, read_state,
+    record_transaction, remaining_emission_budget, remaining_supply, require_role,
+    spendable_balance, Account, Memory, Role, MEMORY_MANAGER,
+};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+const BASIS_POINTS: u64 = 10_000;
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StakingConfig {
+    // Annual reward rate in basis points, e.g. 500 = 5%
+    pub apr_bps: u32,
+    pub min_lock_seconds: u64,
+    pub max_lock_seconds: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct StakePosition {
+    pub id: u64,
+    pub owner: Account,
+    pub amount: Nat,
+    // APR locked in when the stake was opened
+    pub apr_bps: u32,
+    pub staked_at: u64,
+    pub unlock_at: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct StakeArgs {
+    pub from_subaccount: Option<[u8; 32]>,
+    pub amount: Nat,
+    pub lock_duration_seconds: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct StakePositionInfo {
+    pub position: StakePosition,
+    pub accrued_rewards: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct UnstakeResult {
+    pub amount: Nat,
+    pub rewards: Nat,
+    // Mint block paying out the rewards, if any were due
+    pub reward_block: Option<Nat>,
+}
+
+thread_local! {
+    static STAKES: RefCell<StableBTreeMap<u64, StakePosition, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+        )
+    );
+
+    // (owner, position id)
+    static STAKES_BY_OWNER: RefCell<StableBTreeMap<(Account, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        )
+    );
+}
+
+fn positions_of(account: &Account) -> Vec<StakePosition> {
+    let ids: Vec<u64> = STAKES_BY_OWNER.with(|index| {
+        index
+            .borrow()
+            .range((account.clone(), 0)..=(account.clone(), u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+
+    STAKES.with(|stakes| {
+        let stakes = stakes.borrow();
+        ids.into_iter().filter_map(|id| stakes.get(&id)).collect()
+    })
+}
+
+/// Tokens of `account` currently locked in stake positions.
+pub fn staked_balance(account: &Account) -> Nat {
+    positions_of(account)
+        .into_iter()
+        .fold(Nat::from(0u64), |total, position| total + position.amount)
+}
+
+/// Simple (non-compounding) rewards accrued by `position` up to `now`.
+/// Accrual stops at the end of the lockup.
+fn accrued_rewards(position: &StakePosition, now: u64) -> Nat {
+    let elapsed_seconds = now
+        .min(position.unlock_at)
+        .saturating_sub(position.staked_at)
+        / NANOS_PER_SECOND;
+    position.amount.clone() * Nat::from(position.apr_bps) * Nat::from(elapsed_seconds)
+        / Nat::from(BASIS_POINTS * SECONDS_PER_YEAR)
+}
+
+#[update]
+fn stake(args: StakeArgs) -> Result<u64, String> {
+    let config = read_state(|state| state.staking.clone()).ok_or("Staking is not enabled")?;
+    let owner = Account {
+        owner: caller(),
+        subaccount: args.from_subaccount,
+    };
+
+    if freeze::is_frozen(&owner) {
+        return Err("Account is frozen".to_string());
+    }
+    if args.amount == Nat::from(0u64) {
+        return Err("Stake amount must be positive".to_string());
+    }
+    if args.lock_duration_seconds < config.min_lock_seconds
+        || args.lock_duration_seconds > config.max_lock_seconds
+    {
+        return Err(format!(
+            "Lock duration must be between {} and {} seconds",
+            config.min_lock_seconds, config.max_lock_seconds
+        ));
+    }
+
+    let spendable = spendable_balance(&owner);
+    if spendable < args.amount {
+        return Err(format!("Insufficient spendable balance: {}", spendable));
+    }
+
+    let now = time();
+    let id = mutate_state(|state| next_id(&mut state.next_stake_id));
+    let position = StakePosition {
+        id,
+        owner: owner.clone(),
+        amount: args.amount,
+        apr_bps: config.apr_bps,
+        staked_at: now,
+        unlock_at: now.saturating_add(args.lock_duration_seconds.saturating_mul(NANOS_PER_SECOND)),
+    };
+
+    STAKES.with(|stakes| stakes.borrow_mut().insert(id, position));
+    STAKES_BY_OWNER.with(|index| index.borrow_mut().insert((owner, id), ()));
+
+    Ok(id)
+}
+
+/// Closes an unlocked position, releasing the stake and minting its rewards.
+/// Rewards are paid from the current epoch's emission budget; the part that
+/// does not fit in it is forfeited, the stake itself is always released.
+#[update]
+fn unstake(position_id: u64) -> Result<UnstakeResult, String> {
+    let position = STAKES
+        .with(|stakes| stakes.borrow().get(&position_id))
+        .ok_or("Stake position not found")?;
+
+    if position.owner.owner != caller() {
+        return Err("Unauthorized".to_string());
+    }
+    if freeze::is_frozen(&position.owner) {
+        return Err("Account is frozen".to_string());
+    }
+
+    let now = time();
+    if now < position.unlock_at {
+        return Err(format!("Stake is locked until {}", position.unlock_at));
+    }
+
+    // Rewards are capped by whatever room is left under the max supply and in
+    // the current epoch's emission budget
+    let limits = read_state(|state| [remaining_supply(state), remaining_emission_budget(state)]);
+    let rewards = limits
+        .into_iter()
+        .flatten()
+        .fold(accrued_rewards(&position, now), Nat::min);
+    draw_emission_budget(&rewards).expect("rewards fit the emission budget");
+
+    STAKES.with(|stakes| stakes.borrow_mut().remove(&position_id));
+    STAKES_BY_OWNER.with(|index| {
+        index
+            .borrow_mut()
+            .remove(&(position.owner.clone(), position_id))
+    });
+
+    let reward_block = if rewards > Nat::from(0u64) {
+        let tx = Transaction {
+            operation: Operation::Mint {
+                to: position.owner.clone(),
+                amount: rewards.clone(),
+            },
+            fee: None,
+            memo: Some(b"staking reward".to_vec()),
+            created_at_time: None,
+        };
+        apply_transaction(&tx).expect("minting cannot fail");
+        Some(record_transaction(tx))
+    } else {
+        None
+    };
+
+    Ok(UnstakeResult {
+        amount: position.amount,
+        rewards,
+        reward_block,
+    })
+}
+
+#[update]
+fn set_staking_config(config: Option<StakingConfig>) -> Result<(), String> {
+    require_role(Role::Controller)?;
+
+    mutate_state(|state| state.staking = config);
+    Ok(())
+}
+
+#[query]
+fn get_staking_config() -> Option<StakingConfig> {
+    read_state(|state| state.staking.clone())
+}
+
+#[query]
+fn get_stake_positions(account: Account) -> Vec<StakePositionInfo> {
+    let now = time();
+    positions_of(&account)
+        .into_iter()
+        .map(|position| StakePositionInfo {
+            accrued_rewards: accrued_rewards(&position, now),
+            position,
+        })
+        .collect()
+}
@@ -0,0 +1,244 @@
+use candid::{CandidType, Deserialize, Nat};
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use serde::Serialize;
+use std::cell::RefCell;
+
+use crate::icrc3::{Operation, Transaction};
+use crate::{
+    apply_transaction, freeze, mutate_state, next_id, record_transaction, require_role,
+    transfer_fee, Account, Memory, Role, MEMORY_MANAGER,
+};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+// Every debit of a beneficiary walks its schedules to compute the locked
+// balance, so the number of open schedules per beneficiary is bounded
+const MAX_SCHEDULES_PER_BENEFICIARY: usize = 32;
+
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct VestingSchedule {
+    pub id: u64,
+    pub funder: Account,
+    pub beneficiary: Account,
+    pub total: Nat,
+    pub claimed: Nat,
+    // Nanoseconds since the epoch
+    pub start: u64,
+    // Nothing vests before start + cliff; everything has vested at start + duration
+    pub cliff_seconds: u64,
+    pub duration_seconds: u64,
+    // Block that moved the funds to the beneficiary
+    pub funding_block: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct VestingScheduleInfo {
+    pub schedule: VestingSchedule,
+    pub vested: Nat,
+    pub claimable: Nat,
+}
+
+thread_local! {
+    static VESTING_SCHEDULES: RefCell<StableBTreeMap<u64, VestingSchedule, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19))),
+        )
+    );
+
+    // (beneficiary, schedule id)
+    static SCHEDULES_BY_BENEFICIARY: RefCell<StableBTreeMap<(Account, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20))),
+        )
+    );
+}
+
+fn schedule_count(account: &Account) -> usize {
+    SCHEDULES_BY_BENEFICIARY.with(|index| {
+        index
+            .borrow()
+            .range((account.clone(), 0)..=(account.clone(), u64::MAX))
+            .count()
+    })
+}
+
+fn schedules_of(account: &Account) -> Vec<VestingSchedule> {
+    let ids: Vec<u64> = SCHEDULES_BY_BENEFICIARY.with(|index| {
+        index
+            .borrow()
+            .range((account.clone(), 0)..=(account.clone(), u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+
+    VESTING_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        ids.into_iter()
+            .filter_map(|id| schedules.get(&id))
+            .collect()
+    })
+}
+
+/// Amount of `schedule` vested by `now`, vesting linearly after the cliff.
+fn vested_amount(schedule: &VestingSchedule, now: u64) -> Nat {
+    let elapsed_seconds = now.saturating_sub(schedule.start) / NANOS_PER_SECOND;
+    if elapsed_seconds < schedule.cliff_seconds {
+        Nat::from(0u64)
+    } else if elapsed_seconds >= schedule.duration_seconds {
+        schedule.total.clone()
+    } else {
+        schedule.total.clone() * Nat::from(elapsed_seconds) / Nat::from(schedule.duration_seconds)
+    }
+}
+
+/// Tokens of `account` granted by vesting schedules but not yet claimed.
+pub fn vesting_balance(account: &Account) -> Nat {
+    schedules_of(account)
+        .into_iter()
+        .fold(Nat::from(0u64), |total, schedule| {
+            total + schedule.total - schedule.claimed
+        })
+}
+
+/// Moves `total` from the caller's main account to `beneficiary`, where it
+/// stays locked and is released linearly from `start + cliff_seconds` until
+/// `start + duration_seconds`. Released tokens become spendable once claimed.
+/// Only minters, e.g. the tournament payout canister, can create schedules.
+#[update]
+fn create_vesting(
+    beneficiary: Account,
+    total: Nat,
+    start: u64,
+    cliff_seconds: u64,
+    duration_seconds: u64,
+) -> Result<u64, String> {
+    require_role(Role::Minter)?;
+
+    let funder = Account {
+        owner: caller(),
+        subaccount: None,
+    };
+
+    if freeze::is_frozen(&funder) {
+        return Err("Account is frozen".to_string());
+    }
+    if total == Nat::from(0u64) {
+        return Err("Vesting total must be positive".to_string());
+    }
+    if duration_seconds == 0 {
+        return Err("Vesting duration must be positive".to_string());
+    }
+    if cliff_seconds > duration_seconds {
+        return Err("Cliff cannot be longer than the vesting duration".to_string());
+    }
+    if beneficiary == funder {
+        return Err("Cannot create a vesting schedule for yourself".to_string());
+    }
+    if schedule_count(&beneficiary) >= MAX_SCHEDULES_PER_BENEFICIARY {
+        return Err(format!(
+            "Beneficiary already has {} open vesting schedules",
+            MAX_SCHEDULES_PER_BENEFICIARY
+        ));
+    }
+
+    let tx = Transaction {
+        operation: Operation::Transfer {
+            from: funder.clone(),
+            to: beneficiary.clone(),
+            amount: total.clone(),
+            spender: None,
+        },
+        fee: Some(transfer_fee()),
+        memo: Some(b"vesting".to_vec()),
+        created_at_time: None,
+    };
+    apply_transaction(&tx)
+        .map_err(|balance| format!("Insufficient spendable balance: {}", balance))?;
+    let funding_block = record_transaction(tx);
+
+    let id = mutate_state(|state| next_id(&mut state.next_vesting_id));
+    let schedule = VestingSchedule {
+        id,
+        funder,
+        beneficiary: beneficiary.clone(),
+        total,
+        claimed: Nat::from(0u64),
+        start,
+        cliff_seconds,
+        duration_seconds,
+        funding_block,
+    };
+
+    VESTING_SCHEDULES.with(|schedules| schedules.borrow_mut().insert(id, schedule));
+    SCHEDULES_BY_BENEFICIARY.with(|index| index.borrow_mut().insert((beneficiary, id), ()));
+
+    Ok(id)
+}
+
+/// Unlocks everything vested so far across the caller's schedules for
+/// `subaccount`, returning the amount released. Fully claimed schedules are
+/// removed.
+#[update]
+fn claim_vested(subaccount: Option<[u8; 32]>) -> Result<Nat, String> {
+    let beneficiary = Account {
+        owner: caller(),
+        subaccount,
+    };
+    let now = time();
+
+    let mut released = Nat::from(0u64);
+    for mut schedule in schedules_of(&beneficiary) {
+        let claimable = vested_amount(&schedule, now) - schedule.claimed.clone();
+        if claimable == Nat::from(0u64) {
+            continue;
+        }
+        released += claimable.clone();
+        schedule.claimed += claimable;
+
+        if schedule.claimed == schedule.total {
+            VESTING_SCHEDULES.with(|schedules| schedules.borrow_mut().remove(&schedule.id));
+            SCHEDULES_BY_BENEFICIARY.with(|index| {
+                index
+                    .borrow_mut()
+                    .remove(&(beneficiary.clone(), schedule.id))
+            });
+        } else {
+            VESTING_SCHEDULES
+                .with(|schedules| schedules.borrow_mut().insert(schedule.id, schedule));
+        }
+    }
+
+    if released == Nat::from(0u64) {
+        return Err("Nothing to claim".to_string());
+    }
+    Ok(released)
+}
+
+#[query]
+fn get_vesting_schedules(account: Account) -> Vec<VestingScheduleInfo> {
+    let now = time();
+    schedules_of(&account)
+        .into_iter()
+        .map(|schedule| {
+            let vested = vested_amount(&schedule, now);
+            VestingScheduleInfo {
+                claimable: vested.clone() - schedule.claimed.clone(),
+                vested,
+                schedule,
+            }
+        })
+        .collect()
+}
+
+#[query]
+fn get_claimable_vested(account: Account) -> Nat {
+    let now = time();
+    schedules_of(&account)
+        .into_iter()
+        .fold(Nat::from(0u64), |total, schedule| {
+            total + vested_amount(&schedule, now) - schedule.claimed
+        })
+}
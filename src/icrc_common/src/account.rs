@@ -0,0 +1,277 @@
+use candid::{CandidType, Deserialize, Principal};
+use data_encoding::BASE32_NOPAD;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+// ICRC-1 account, shared by every canister that talks to the ledger.
+// Equality, ordering and hashing treat a missing subaccount as the all-zero
+// default, so both spellings of an account are the same map key.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+pub struct Account {
+    pub owner: Principal,
+    pub subaccount: Option<[u8; 32]>,
+}
+
+impl Account {
+    /// Subaccount with `None` normalized to the all-zero default.
+    pub fn effective_subaccount(&self) -> [u8; 32] {
+        self.subaccount.unwrap_or([0; 32])
+    }
+
+    /// The account with the default subaccount spelled as `None`.
+    pub fn canonical(self) -> Self {
+        let subaccount = self.subaccount.filter(|subaccount| *subaccount != [0; 32]);
+        Account {
+            owner: self.owner,
+            subaccount,
+        }
+    }
+}
+
+impl PartialEq for Account {
+    fn eq(&self, other: &Self) -> bool {
+        self.owner == other.owner && self.effective_subaccount() == other.effective_subaccount()
+    }
+}
+
+impl Eq for Account {}
+
+impl Ord for Account {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.owner.cmp(&other.owner).then_with(|| {
+            self.effective_subaccount()
+                .cmp(&other.effective_subaccount())
+        })
+    }
+}
+
+impl PartialOrd for Account {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Account {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.owner.hash(state);
+        self.effective_subaccount().hash(state);
+    }
+}
+
+impl From<Principal> for Account {
+    fn from(owner: Principal) -> Self {
+        Account {
+            owner,
+            subaccount: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AccountParseError {
+    MalformedPrincipal(String),
+    MalformedSubaccount(String),
+    BadChecksum,
+    // Valid but not the canonical encoding, e.g. hex with leading zeros
+    NotCanonical,
+}
+
+impl fmt::Display for AccountParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountParseError::MalformedPrincipal(message) => {
+                write!(f, "Malformed principal: {}", message)
+            }
+            AccountParseError::MalformedSubaccount(message) => {
+                write!(f, "Malformed subaccount: {}", message)
+            }
+            AccountParseError::BadChecksum => write!(f, "Account checksum does not match"),
+            AccountParseError::NotCanonical => write!(f, "Account is not in canonical form"),
+        }
+    }
+}
+
+impl std::error::Error for AccountParseError {}
+
+/// CRC32 of the owner and subaccount bytes as lowercase, unpadded base32.
+fn checksum(owner: &Principal, subaccount: &[u8; 32]) -> String {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(owner.as_slice());
+    hasher.update(subaccount);
+    BASE32_NOPAD
+        .encode(&hasher.finalize().to_be_bytes())
+        .to_lowercase()
+}
+
+/// ICRC-1 textual encoding: the bare principal for the default subaccount,
+/// otherwise `<principal>-<checksum>.<subaccount hex without leading zeros>`.
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.subaccount {
+            Some(subaccount) if subaccount != [0; 32] => {
+                let hex: String = subaccount.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(
+                    f,
+                    "{}-{}.{}",
+                    self.owner,
+                    checksum(&self.owner, &subaccount),
+                    hex.trim_start_matches('0')
+                )
+            }
+            _ => write!(f, "{}", self.owner),
+        }
+    }
+}
+
+impl FromStr for Account {
+    type Err = AccountParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parse_principal = |text: &str| {
+            Principal::from_text(text)
+                .map_err(|err| AccountParseError::MalformedPrincipal(err.to_string()))
+        };
+
+        let Some((owner_and_checksum, hex)) = text.rsplit_once('.') else {
+            return Ok(Account::from(parse_principal(text)?));
+        };
+        let (owner, expected_checksum) = owner_and_checksum
+            .rsplit_once('-')
+            .ok_or(AccountParseError::BadChecksum)?;
+        let owner = parse_principal(owner)?;
+
+        if hex.is_empty() || hex.len() > 64 {
+            return Err(AccountParseError::MalformedSubaccount(format!(
+                "expected 1 to 64 hex digits, got {}",
+                hex.len()
+            )));
+        }
+        if hex.starts_with('0') {
+            return Err(AccountParseError::NotCanonical);
+        }
+        if !hex
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        {
+            return Err(AccountParseError::MalformedSubaccount(
+                "expected lowercase hex digits".to_string(),
+            ));
+        }
+
+        let padded = format!("{:0>64}", hex);
+        let mut subaccount = [0u8; 32];
+        for (i, byte) in subaccount.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&padded[2 * i..2 * i + 2], 16)
+                .expect("hex digits were validated");
+        }
+
+        if checksum(&owner, &subaccount) != expected_checksum {
+            return Err(AccountParseError::BadChecksum);
+        }
+
+        Ok(Account {
+            owner,
+            subaccount: Some(subaccount),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+
+    fn account(subaccount: Option<[u8; 32]>) -> Account {
+        Account {
+            owner: Principal::from_text(OWNER).unwrap(),
+            subaccount,
+        }
+    }
+
+    fn counting_subaccount() -> [u8; 32] {
+        let mut subaccount = [0u8; 32];
+        for (i, byte) in subaccount.iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+        subaccount
+    }
+
+    #[test]
+    fn encodes_standard_vectors() {
+        let mut one = [0u8; 32];
+        one[31] = 1;
+
+        assert_eq!(account(None).to_string(), OWNER);
+        assert_eq!(account(Some([0; 32])).to_string(), OWNER);
+        assert_eq!(
+            account(Some(one)).to_string(),
+            format!("{}-6cc627i.1", OWNER)
+        );
+        assert_eq!(
+            account(Some(counting_subaccount())).to_string(),
+            format!(
+                "{}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20",
+                OWNER
+            )
+        );
+    }
+
+    #[test]
+    fn decodes_standard_vectors() {
+        let mut one = [0u8; 32];
+        one[31] = 1;
+
+        assert_eq!(Account::from_str(OWNER), Ok(account(None)));
+        assert_eq!(
+            Account::from_str(&format!("{}-6cc627i.1", OWNER)),
+            Ok(account(Some(one)))
+        );
+        assert_eq!(
+            Account::from_str(&format!(
+                "{}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20",
+                OWNER
+            )),
+            Ok(account(Some(counting_subaccount())))
+        );
+    }
+
+    #[test]
+    fn default_subaccount_spellings_are_equal() {
+        let mut one = [0u8; 32];
+        one[31] = 1;
+
+        assert_eq!(account(None), account(Some([0; 32])));
+        assert_eq!(account(None).cmp(&account(Some([0; 32]))), Ordering::Equal);
+        assert!(account(Some([0; 32])) < account(Some(one)));
+        assert_eq!(account(Some([0; 32])).canonical().subaccount, None);
+        assert_eq!(account(Some(one)).canonical().subaccount, Some(one));
+    }
+
+    #[test]
+    fn rejects_invalid_text() {
+        assert_eq!(
+            Account::from_str(&format!("{}-6cc627j.1", OWNER)),
+            Err(AccountParseError::BadChecksum)
+        );
+        assert_eq!(
+            Account::from_str(&format!("{}-6cc627i.01", OWNER)),
+            Err(AccountParseError::NotCanonical)
+        );
+        assert!(matches!(
+            Account::from_str(&format!("{}-6cc627i.", OWNER)),
+            Err(AccountParseError::MalformedSubaccount(_))
+        ));
+        assert!(matches!(
+            Account::from_str(&format!("{}-6cc627i.1G", OWNER)),
+            Err(AccountParseError::MalformedSubaccount(_))
+        ));
+        assert!(matches!(
+            Account::from_str("not-a-principal"),
+            Err(AccountParseError::MalformedPrincipal(_))
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+use candid::Nat;
+use ic_stable_structures::{Memory, StableBTreeMap, StableLog};
+
+use crate::icrc3::{BlockWithId, GetBlocksArgs, GetBlocksResult};
+use crate::Value;
+
+// Deduplication window for transactions carrying `created_at_time`
+pub const TX_WINDOW_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000; // 24 hours
+pub const PERMITTED_DRIFT_NANOS: u64 = 60 * 1_000_000_000; // 1 minute
+const MAX_DEDUP_PRUNE_PER_CALL: usize = 100;
+
+// Upper bound on blocks returned by a single block query
+pub const MAX_BLOCKS_PER_RESPONSE: u64 = 1_000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TimeError {
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+}
+
+/// Rejects a `created_at_time` outside the deduplication window. Transactions
+/// without one are always accepted.
+pub fn check_created_at_time(created_at_time: Option<u64>, now: u64) -> Result<(), TimeError> {
+    match created_at_time {
+        Some(created) if created.saturating_add(TX_WINDOW_NANOS + PERMITTED_DRIFT_NANOS) < now => {
+            Err(TimeError::TooOld)
+        }
+        Some(created) if created > now.saturating_add(PERMITTED_DRIFT_NANOS) => {
+            Err(TimeError::CreatedInFuture { ledger_time: now })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Append-only ICRC-3 block log, together with the hashes of recent
+/// transactions used to deduplicate them.
+pub struct BlockLog<M: Memory> {
+    blocks: StableLog<Value, M, M>,
+    // Transaction hash -> index of the block that recorded it
+    tx_hashes: StableBTreeMap<[u8; 32], u64, M>,
+    // (created_at_time, transaction hash), used to expire tx_hashes entries
+    tx_hash_expiry: StableBTreeMap<(u64, [u8; 32]), (), M>,
+}
+
+impl<M: Memory> BlockLog<M> {
+    pub fn init(
+        index_memory: M,
+        data_memory: M,
+        tx_hashes_memory: M,
+        tx_hash_expiry_memory: M,
+    ) -> Self {
+        Self {
+            blocks: StableLog::init(index_memory, data_memory)
+                .expect("failed to initialize the block log"),
+            tx_hashes: StableBTreeMap::init(tx_hashes_memory),
+            tx_hash_expiry: StableBTreeMap::init(tx_hash_expiry_memory),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.len() == 0
+    }
+
+    pub fn get(&self, index: u64) -> Option<Value> {
+        self.blocks.get(index)
+    }
+
+    pub fn last_block(&self) -> Option<(u64, Value)> {
+        let index = self.len().checked_sub(1)?;
+        self.get(index).map(|block| (index, block))
+    }
+
+    /// Appends `block` and returns its index. `dedup_key` is the
+    /// `(created_at_time, transaction hash)` of a transaction that must not
+    /// be recorded twice within the deduplication window.
+    pub fn append(&mut self, block: &Value, dedup_key: Option<(u64, [u8; 32])>) -> u64 {
+        let index = self
+            .blocks
+            .append(block)
+            .expect("failed to append block to the log");
+
+        if let Some((created_at_time, tx_hash)) = dedup_key {
+            self.tx_hashes.insert(tx_hash, index);
+            self.tx_hash_expiry.insert((created_at_time, tx_hash), ());
+        }
+        index
+    }
+
+    /// Index of the block that recorded the transaction hashing to `tx_hash`
+    /// within the deduplication window.
+    pub fn find_duplicate(&mut self, tx_hash: &[u8; 32], now: u64) -> Option<u64> {
+        self.prune_tx_hashes(now);
+        self.tx_hashes.get(tx_hash)
+    }
+
+    /// Drops remembered transaction hashes that have left the deduplication
+    /// window, a bounded number per call.
+    fn prune_tx_hashes(&mut self, now: u64) {
+        let cutoff = now.saturating_sub(TX_WINDOW_NANOS + PERMITTED_DRIFT_NANOS);
+        let expired: Vec<(u64, [u8; 32])> = self
+            .tx_hash_expiry
+            .iter()
+            .take_while(|((created_at_time, _), _)| *created_at_time < cutoff)
+            .take(MAX_DEDUP_PRUNE_PER_CALL)
+            .map(|(key, _)| key)
+            .collect();
+
+        for key in expired {
+            self.tx_hash_expiry.remove(&key);
+            self.tx_hashes.remove(&key.1);
+        }
+    }
+
+    /// Answers `icrc3_get_blocks`, returning at most
+    /// `MAX_BLOCKS_PER_RESPONSE` blocks across all requested ranges.
+    pub fn get_blocks(&self, args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+        let log_length = self.len();
+        let mut budget = MAX_BLOCKS_PER_RESPONSE;
+        let mut blocks = Vec::new();
+
+        for range in args {
+            let start = u64::try_from(range.start.0).unwrap_or(u64::MAX);
+            let length = u64::try_from(range.length.0).unwrap_or(u64::MAX);
+            let end = start.saturating_add(length.min(budget)).min(log_length);
+
+            blocks.extend(self.blocks_with_ids(start..end));
+            budget -= end.saturating_sub(start);
+        }
+
+        GetBlocksResult {
+            log_length: Nat::from(log_length),
+            blocks,
+            archived_blocks: Vec::new(),
+        }
+    }
+
+    /// Blocks at the given indices, skipping indices past the end of the log.
+    pub fn blocks_with_ids(&self, indices: impl IntoIterator<Item = u64>) -> Vec<BlockWithId> {
+        indices
+            .into_iter()
+            .filter_map(|index| {
+                self.get(index).map(|block| BlockWithId {
+                    id: Nat::from(index),
+                    block,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_stable_structures::VectorMemory;
+
+    const NOW: u64 = 1_700_000_000_000_000_000;
+
+    fn block_log() -> BlockLog<VectorMemory> {
+        BlockLog::init(
+            VectorMemory::default(),
+            VectorMemory::default(),
+            VectorMemory::default(),
+            VectorMemory::default(),
+        )
+    }
+
+    fn block(n: u64) -> Value {
+        Value::Nat(Nat::from(n))
+    }
+
+    #[test]
+    fn accepts_created_at_time_within_the_window() {
+        assert_eq!(check_created_at_time(None, NOW), Ok(()));
+        assert_eq!(
+            check_created_at_time(Some(NOW - TX_WINDOW_NANOS), NOW),
+            Ok(())
+        );
+        assert_eq!(
+            check_created_at_time(Some(NOW - TX_WINDOW_NANOS - PERMITTED_DRIFT_NANOS - 1), NOW),
+            Err(TimeError::TooOld)
+        );
+        assert_eq!(
+            check_created_at_time(Some(NOW + PERMITTED_DRIFT_NANOS + 1), NOW),
+            Err(TimeError::CreatedInFuture { ledger_time: NOW })
+        );
+    }
+
+    #[test]
+    fn forgets_duplicates_once_they_leave_the_window() {
+        let mut log = block_log();
+        let tx_hash = [7; 32];
+        log.append(&block(0), None);
+        let index = log.append(&block(1), Some((NOW, tx_hash)));
+
+        assert_eq!(log.find_duplicate(&tx_hash, NOW), Some(index));
+        assert_eq!(log.find_duplicate(&[8; 32], NOW), None);
+
+        let expired = NOW + TX_WINDOW_NANOS + PERMITTED_DRIFT_NANOS + 1;
+        assert_eq!(log.find_duplicate(&tx_hash, expired), None);
+        assert_eq!(log.last_block().map(|(index, _)| index), Some(1));
+    }
+
+    #[test]
+    fn caps_blocks_returned_across_ranges() {
+        let mut log = block_log();
+        for n in 0..MAX_BLOCKS_PER_RESPONSE + 10 {
+            log.append(&block(n), None);
+        }
+        let range = |start: u64, length: u64| GetBlocksArgs {
+            start: Nat::from(start),
+            length: Nat::from(length),
+        };
+
+        let result = log.get_blocks(vec![range(0, 600), range(600, 600)]);
+        assert_eq!(result.log_length, Nat::from(MAX_BLOCKS_PER_RESPONSE + 10));
+        assert_eq!(result.blocks.len() as u64, MAX_BLOCKS_PER_RESPONSE);
+        assert_eq!(
+            result.blocks.last().map(|block| block.id.clone()),
+            Some(Nat::from(MAX_BLOCKS_PER_RESPONSE - 1))
+        );
+    }
+}
@@ -0,0 +1,21 @@
+use candid::{CandidType, Deserialize};
+
+pub const ICRC10_URL: &str = "https://github.com/dfinity/ICRC/blob/main/ICRCs/ICRC-10/ICRC-10.md";
+
+// Entry returned by icrc10_supported_standards
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StandardRecord {
+    pub name: String,
+    pub url: String,
+}
+
+/// Builds ICRC-10 records from (name, url) pairs.
+pub fn standards(entries: &[(&str, &str)]) -> Vec<StandardRecord> {
+    entries
+        .iter()
+        .map(|(name, url)| StandardRecord {
+            name: name.to_string(),
+            url: url.to_string(),
+        })
+        .collect()
+}
@@ -0,0 +1,270 @@
+use candid::{CandidType, Deserialize, Nat};
+
+// ICRC-21 consent message types
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ConsentMessageMetadata {
+    pub language: String,
+    pub utc_offset_minutes: Option<i16>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum DisplayMessageType {
+    GenericDisplay,
+    LineDisplay {
+        characters_per_line: u16,
+        lines_per_page: u16,
+    },
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ConsentMessageSpec {
+    pub metadata: ConsentMessageMetadata,
+    pub device_spec: Option<DisplayMessageType>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ConsentMessageRequest {
+    pub method: String,
+    pub arg: Vec<u8>,
+    pub user_preferences: ConsentMessageSpec,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct LineDisplayPage {
+    pub lines: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum ConsentMessage {
+    GenericDisplayMessage(String),
+    LineDisplayMessage { pages: Vec<LineDisplayPage> },
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ConsentInfo {
+    pub consent_message: ConsentMessage,
+    pub metadata: ConsentMessageMetadata,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ErrorInfo {
+    pub description: String,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum Icrc21Error {
+    UnsupportedCanisterCall(ErrorInfo),
+    ConsentMessageUnavailable(ErrorInfo),
+    InsufficientPayment(ErrorInfo),
+    GenericError {
+        error_code: Nat,
+        description: String,
+    },
+}
+
+pub const ICRC21_URL: &str = "https://github.com/dfinity/wg-identity-authentication/blob/main/topics/ICRC-21/icrc_21_consent_msg.md";
+
+impl Icrc21Error {
+    pub fn unsupported(method: &str) -> Self {
+        Icrc21Error::UnsupportedCanisterCall(ErrorInfo {
+            description: format!("No consent message is available for {}", method),
+        })
+    }
+
+    pub fn bad_arg(method: &str, err: candid::Error) -> Self {
+        Icrc21Error::UnsupportedCanisterCall(ErrorInfo {
+            description: format!("Failed to decode the arguments of {}: {}", method, err),
+        })
+    }
+
+    pub fn unavailable(description: impl Into<String>) -> Self {
+        Icrc21Error::ConsentMessageUnavailable(ErrorInfo {
+            description: description.into(),
+        })
+    }
+}
+
+/// Formats a token amount in base units with `decimals` places, dropping
+/// trailing zeros, e.g. 150_000_000 with 8 decimals is "1.5".
+pub fn format_amount(amount: &Nat, decimals: u8) -> String {
+    let digits = amount.0.to_string();
+    let decimals = decimals as usize;
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Formats nanoseconds since the Unix epoch as a date and time, shifted by
+/// `utc_offset_minutes` when the signer provided one.
+pub fn format_timestamp(nanos: u64, utc_offset_minutes: Option<i16>) -> String {
+    let offset = utc_offset_minutes.unwrap_or(0) as i64;
+    let minutes = (nanos / 60_000_000_000) as i64 + offset;
+    let (days, minute_of_day) = (minutes.div_euclid(1440), minutes.rem_euclid(1440));
+
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let zone = match offset {
+        0 => "UTC".to_string(),
+        _ => format!(
+            "UTC{}{:02}:{:02}",
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 60,
+            offset.abs() % 60
+        ),
+    };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} {}",
+        year,
+        month,
+        day,
+        minute_of_day / 60,
+        minute_of_day % 60,
+        zone
+    )
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        // Words longer than a line are split across lines
+        while word.len() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if word.is_empty() {
+            continue;
+        }
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Renders a titled list of labelled fields for the display requested in
+/// `spec`. Messages are only available in English.
+pub fn consent_info(
+    spec: &ConsentMessageSpec,
+    title: &str,
+    fields: &[(&str, String)],
+) -> ConsentInfo {
+    let consent_message = match spec.device_spec {
+        Some(DisplayMessageType::LineDisplay {
+            characters_per_line,
+            lines_per_page,
+        }) => {
+            let mut lines = wrap(title, characters_per_line as usize);
+            for (label, value) in fields {
+                lines.extend(wrap(&format!("{}:", label), characters_per_line as usize));
+                lines.extend(wrap(value, characters_per_line as usize));
+            }
+            let pages = lines
+                .chunks((lines_per_page as usize).max(1))
+                .map(|lines| LineDisplayPage {
+                    lines: lines.to_vec(),
+                })
+                .collect();
+            ConsentMessage::LineDisplayMessage { pages }
+        }
+        _ => {
+            let mut message = format!("# {}", title);
+            for (label, value) in fields {
+                message.push_str(&format!("\n\n**{}:**\n{}", label, value));
+            }
+            ConsentMessage::GenericDisplayMessage(message)
+        }
+    };
+
+    ConsentInfo {
+        consent_message,
+        metadata: ConsentMessageMetadata {
+            language: "en".to_string(),
+            utc_offset_minutes: spec.metadata.utc_offset_minutes,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_amounts() {
+        assert_eq!(format_amount(&Nat::from(0u64), 8), "0");
+        assert_eq!(format_amount(&Nat::from(1u64), 8), "0.00000001");
+        assert_eq!(format_amount(&Nat::from(150_000_000u64), 8), "1.5");
+        assert_eq!(format_amount(&Nat::from(50_000_000_000u64), 8), "500");
+        assert_eq!(format_amount(&Nat::from(42u64), 0), "42");
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(format_timestamp(0, None), "1970-01-01 00:00 UTC");
+        // 2024-02-29 23:30 UTC
+        let nanos = 1_709_249_400 * 1_000_000_000;
+        assert_eq!(format_timestamp(nanos, None), "2024-02-29 23:30 UTC");
+        assert_eq!(
+            format_timestamp(nanos, Some(60)),
+            "2024-03-01 00:30 UTC+01:00"
+        );
+        assert_eq!(
+            format_timestamp(nanos, Some(-330)),
+            "2024-02-29 18:00 UTC-05:30"
+        );
+    }
+
+    #[test]
+    fn pages_line_display_messages() {
+        let spec = ConsentMessageSpec {
+            metadata: ConsentMessageMetadata {
+                language: "en".to_string(),
+                utc_offset_minutes: None,
+            },
+            device_spec: Some(DisplayMessageType::LineDisplay {
+                characters_per_line: 10,
+                lines_per_page: 2,
+            }),
+        };
+
+        let info = consent_info(&spec, "Send GAME", &[("Amount", "1.5 GAME".to_string())]);
+        let ConsentMessage::LineDisplayMessage { pages } = info.consent_message else {
+            panic!("expected a line display message");
+        };
+        let pages: Vec<Vec<String>> = pages.into_iter().map(|page| page.lines).collect();
+        assert_eq!(
+            pages,
+            vec![
+                vec!["Send GAME".to_string(), "Amount:".to_string()],
+                vec!["1.5 GAME".to_string()],
+            ]
+        );
+    }
+}
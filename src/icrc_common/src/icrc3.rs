@@ -0,0 +1,159 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ciborium::value::Value as Cbor;
+use sha2::{Digest, Sha256};
+
+use crate::Value;
+
+// ICRC-3 query types
+#[derive(CandidType, Deserialize, Clone)]
+pub struct GetBlocksArgs {
+    pub start: Nat,
+    pub length: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: Value,
+}
+
+candid::define_function!(pub GetBlocksCallback : (Vec<GetBlocksArgs>) -> (GetBlocksResult) query);
+
+#[derive(CandidType, Deserialize)]
+pub struct ArchivedBlocks {
+    pub args: Vec<GetBlocksArgs>,
+    pub callback: GetBlocksCallback,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct GetArchivesArgs {
+    pub from: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ArchiveInfo {
+    pub canister_id: Principal,
+    pub start: Nat,
+    pub end: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct DataCertificate {
+    pub certificate: Vec<u8>,
+    pub hash_tree: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct SupportedBlockType {
+    pub block_type: String,
+    pub url: String,
+}
+
+// Certification of the chain tip.
+//
+// The certified tree has exactly two leaves:
+//   last_block_hash  -> hash of the newest block
+//   last_block_index -> leb128 index of the newest block
+// Labels are kept in lexicographic order as required by the IC hash tree.
+
+fn domain_hash(domain: &str) -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.update([domain.len() as u8]);
+    hasher.update(domain.as_bytes());
+    hasher
+}
+
+fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = domain_hash("ic-hashtree-leaf");
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn labeled_hash(label: &[u8], subtree: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = domain_hash("ic-hashtree-labeled");
+    hasher.update(label);
+    hasher.update(subtree);
+    hasher.finalize().into()
+}
+
+fn fork_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = domain_hash("ic-hashtree-fork");
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn leb128_index(index: u64) -> Vec<u8> {
+    let mut buf = Vec::new();
+    Nat::from(index)
+        .encode(&mut buf)
+        .expect("failed to leb128-encode block index");
+    buf
+}
+
+/// Root hash to pass to `set_certified_data` for the given chain tip.
+pub fn tip_root_hash(last_block_index: u64, last_block_hash: &[u8; 32]) -> [u8; 32] {
+    let hash_leaf = labeled_hash(b"last_block_hash", &leaf_hash(last_block_hash));
+    let index_leaf = labeled_hash(
+        b"last_block_index",
+        &leaf_hash(&leb128_index(last_block_index)),
+    );
+    fork_hash(&hash_leaf, &index_leaf)
+}
+
+/// CBOR-encoded hash tree matching `tip_root_hash`.
+pub fn tip_hash_tree(last_block_index: u64, last_block_hash: &[u8; 32]) -> Vec<u8> {
+    let leaf = |data: Vec<u8>| Cbor::Array(vec![Cbor::Integer(3.into()), Cbor::Bytes(data)]);
+    let labeled = |label: &[u8], subtree: Cbor| {
+        Cbor::Array(vec![
+            Cbor::Integer(2.into()),
+            Cbor::Bytes(label.to_vec()),
+            subtree,
+        ])
+    };
+
+    let tree = Cbor::Array(vec![
+        Cbor::Integer(1.into()),
+        labeled(b"last_block_hash", leaf(last_block_hash.to_vec())),
+        labeled(b"last_block_index", leaf(leb128_index(last_block_index))),
+    ]);
+
+    let mut buf = Vec::new();
+    ciborium::ser::into_writer(&Cbor::Tag(55799, Box::new(tree)), &mut buf)
+        .expect("failed to encode hash tree");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_tip_hash_tree_as_self_described_cbor() {
+        let tree = tip_hash_tree(1, &[0; 32]);
+        assert_eq!(&tree[..3], &[0xd9, 0xd9, 0xf7]);
+
+        let Cbor::Tag(55799, tree) = ciborium::de::from_reader(tree.as_slice()).unwrap() else {
+            panic!("expected the self-describe tag");
+        };
+        let Cbor::Array(fork) = *tree else {
+            panic!("expected a fork node");
+        };
+        assert_eq!(fork.len(), 3);
+        assert_eq!(fork[0], Cbor::Integer(1.into()));
+    }
+
+    #[test]
+    fn tip_root_hash_depends_on_index_and_hash() {
+        let root = tip_root_hash(1, &[0; 32]);
+        assert_ne!(root, tip_root_hash(2, &[0; 32]));
+        assert_ne!(root, tip_root_hash(1, &[1; 32]));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod account;
+pub mod block_log;
+pub mod icrc10;
+pub mod icrc21;
+pub mod icrc3;
+pub mod value;
+
+pub use account::{Account, AccountParseError};
+pub use value::Value;
@@ -0,0 +1,145 @@
+use candid::{CandidType, Deserialize, Int, Nat};
+use ic_stable_structures::storable::{Bound, Storable};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+
+use crate::Account;
+
+// ICRC-3 generic value, used for ledger blocks and ICRC-7 metadata
+#[derive(CandidType, Deserialize, Clone, Serialize)]
+pub enum Value {
+    Blob(Vec<u8>),
+    Text(String),
+    Nat(Nat),
+    Int(Int),
+    Array(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Representation-independent hash as defined by ICRC-3.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        match self {
+            Value::Blob(bytes) => hasher.update(bytes),
+            Value::Text(text) => hasher.update(text.as_bytes()),
+            Value::Nat(nat) => {
+                let mut buf = Vec::new();
+                nat.encode(&mut buf).expect("failed to leb128-encode nat");
+                hasher.update(buf);
+            }
+            Value::Int(int) => {
+                let mut buf = Vec::new();
+                int.encode(&mut buf).expect("failed to sleb128-encode int");
+                hasher.update(buf);
+            }
+            Value::Array(values) => {
+                for value in values {
+                    hasher.update(value.hash());
+                }
+            }
+            Value::Map(entries) => {
+                let mut pairs: Vec<Vec<u8>> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let mut pair = Sha256::digest(key.as_bytes()).to_vec();
+                        pair.extend_from_slice(&value.hash());
+                        pair
+                    })
+                    .collect();
+                pairs.sort();
+                for pair in pairs {
+                    hasher.update(pair);
+                }
+            }
+        }
+        hasher.finalize().into()
+    }
+}
+
+// Blocks are kept in stable memory in their Candid encoding
+impl Storable for Value {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(candid::encode_one(self).expect("failed to encode value"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("failed to decode value")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl From<&Account> for Value {
+    fn from(account: &Account) -> Self {
+        // Encode the default subaccount one way so equal accounts hash equally
+        let account = account.clone().canonical();
+        let mut parts = vec![Value::Blob(account.owner.as_slice().to_vec())];
+        if let Some(subaccount) = account.subaccount {
+            parts.push(Value::Blob(subaccount.to_vec()));
+        }
+        Value::Array(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(hash: [u8; 32]) -> String {
+        hash.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn hashes_standard_vectors() {
+        assert_eq!(
+            hex(Value::Nat(Nat::from(42u64)).hash()),
+            "684888c0ebb17f374298b65ee2807526c066094c701bcc7ebbe1c1095f494fc1"
+        );
+        assert_eq!(
+            hex(Value::Int(Int::from(-42)).hash()),
+            "de5a6f78116eca62d7fc5ce159d23ae6b889b365a1739ad2cf36f925a140d0cc"
+        );
+        assert_eq!(
+            hex(Value::Text("Hello, World!".to_string()).hash()),
+            "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+        );
+        assert_eq!(
+            hex(Value::Blob(vec![1, 2, 3, 4]).hash()),
+            "9f64a747e1b97f131fabb6b447296c9b6f0201e79fb3c5356e6c77e89b6a806a"
+        );
+        assert_eq!(
+            hex(Value::Array(vec![
+                Value::Nat(Nat::from(42u64)),
+                Value::Text("Hello, World!".to_string()),
+            ])
+            .hash()),
+            "89cab1805c521f64c07240f79a0df93d3ae5727050277dc9d88b6de47ea9a345"
+        );
+    }
+
+    #[test]
+    fn map_hash_ignores_entry_order() {
+        let a = ("a".to_string(), Value::Nat(Nat::from(1u64)));
+        let b = ("b".to_string(), Value::Text("two".to_string()));
+        assert_eq!(
+            Value::Map(vec![a.clone(), b.clone()]).hash(),
+            Value::Map(vec![b, a]).hash()
+        );
+    }
+
+    #[test]
+    fn default_subaccount_spellings_hash_equally() {
+        let owner = candid::Principal::from_slice(&[1, 2, 3]);
+        let implicit = Account {
+            owner,
+            subaccount: None,
+        };
+        let explicit = Account {
+            owner,
+            subaccount: Some([0; 32]),
+        };
+        assert_eq!(Value::from(&implicit).hash(), Value::from(&explicit).hash());
+    }
+}
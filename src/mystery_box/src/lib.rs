@@ -1,7 +1,11 @@
 use candid::{CandidType, Deserialize, Nat, Principal};
 use ic_cdk::{caller, query, update};
+use icrc_common::icrc10::{self, StandardRecord};
+use icrc_common::icrc21::{
+    self, consent_info, format_amount, ConsentInfo, ConsentMessageRequest, Icrc21Error,
+};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
 use serde::Serialize;
 use sha2::{Digest, Sha256};
 use std::cell::RefCell;
@@ -43,6 +47,15 @@ pub struct CardTemplate {
     pub image_url: String,
 }
 
+// Token box prices are paid in, with the symbol and decimals consent
+// messages render prices with
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct GameTokenInfo {
+    pub canister: Principal,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
 #[derive(CandidType, Deserialize)]
 pub struct OpenBoxArgs {
     pub box_id: Nat,
@@ -55,6 +68,33 @@ pub struct BoxOpenResult {
     pub card_details: CardTemplate,
 }
 
+// ICRC-2 types used to collect payment from the game token canister
+pub use icrc_common::Account;
+
+#[derive(CandidType, Deserialize)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<[u8; 32]>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
@@ -68,7 +108,19 @@ thread_local! {
     static NEXT_BOX_ID: RefCell<Nat> = RefCell::new(Nat::from(1u64));
     
     static PLAYER_NFT_CANISTER: RefCell<Option<Principal>> = RefCell::new(None);
-    static GAME_TOKEN_CANISTER: RefCell<Option<Principal>> = RefCell::new(None);
+
+    // Kept in stable memory so consent messages keep rendering prices after
+    // an upgrade
+    static GAME_TOKEN: RefCell<StableCell<Option<GameTokenInfo>, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
+            None,
+        ).expect("failed to initialize the game token settings")
+    );
+}
+
+fn game_token() -> Option<GameTokenInfo> {
+    GAME_TOKEN.with(|game_token| game_token.borrow().get().clone())
 }
 
 #[update]
@@ -113,17 +165,37 @@ async fn open_mystery_box(args: OpenBoxArgs) -> Result<BoxOpenResult, String> {
         return Err("No more boxes available".to_string());
     }
 
-    // Check payment (simplified - should check game token balance)
-    let game_token_canister = GAME_TOKEN_CANISTER.with(|canister| *canister.borrow())
+    let game_token_canister = game_token()
+        .map(|game_token| game_token.canister)
         .ok_or("Game token canister not set")?;
 
-    // Burn tokens for payment
-    let _burn_result: Result<(Result<(), String>,), _> = ic_cdk::call(
+    // Pull payment from the player into the minting account, which burns it;
+    // requires a prior icrc2_approve for this canister
+    let minting_account = game_token_minting_account(game_token_canister).await?;
+    let payment_args = TransferFromArgs {
+        spender_subaccount: None,
+        from: Account {
+            owner: caller,
+            subaccount: None,
+        },
+        to: minting_account,
+        amount: mystery_box.price.clone(),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+
+    let payment_result: Result<(Result<Nat, TransferFromError>,), _> = ic_cdk::call(
         game_token_canister,
-        "burn_game_tokens",
-        (caller, mystery_box.price.clone()),
+        "icrc2_transfer_from",
+        (payment_args,),
     ).await;
 
+    payment_result
+        .map_err(|e| format!("Failed to call game token canister: {:?}", e))?
+        .0
+        .map_err(|e| format!("Payment failed: {:?}", e))?;
+
     // Generate random card
     let random_seed = get_random_seed().await?;
     let selected_card = select_random_card(&mystery_box, random_seed)?;
@@ -165,6 +237,14 @@ async fn open_mystery_box(args: OpenBoxArgs) -> Result<BoxOpenResult, String> {
     })
 }
 
+async fn game_token_minting_account(game_token_canister: Principal) -> Result<Account, String> {
+    let (minting_account,): (Option<Account>,) =
+        ic_cdk::call(game_token_canister, "icrc1_minting_account", ())
+            .await
+            .map_err(|e| format!("Failed to call game token canister: {:?}", e))?;
+    minting_account.ok_or_else(|| "Game token has no minting account".to_string())
+}
+
 async fn get_random_seed() -> Result<[u8; 32], String> {
     let (random_bytes,): (Vec<u8>,) = ic_cdk::call(
         Principal::management_canister(),
@@ -253,12 +333,87 @@ async fn set_player_nft_canister(canister_id: Principal) -> Result<(), String> {
 
 #[update]
 async fn set_game_token_canister(canister_id: Principal) -> Result<(), String> {
-    GAME_TOKEN_CANISTER.with(|canister| {
-        *canister.borrow_mut() = Some(canister_id);
+    // Box prices are in the token's base units; consent messages render them
+    // with its own symbol and decimals
+    let (symbol,): (String,) = ic_cdk::call(canister_id, "icrc1_symbol", ())
+        .await
+        .map_err(|e| format!("Failed to call game token canister: {:?}", e))?;
+    let (decimals,): (u8,) = ic_cdk::call(canister_id, "icrc1_decimals", ())
+        .await
+        .map_err(|e| format!("Failed to call game token canister: {:?}", e))?;
+
+    let info = GameTokenInfo {
+        canister: canister_id,
+        symbol,
+        decimals,
+    };
+    GAME_TOKEN.with(|game_token| {
+        game_token
+            .borrow_mut()
+            .set(Some(info))
+            .expect("failed to persist the game token settings")
     });
     Ok(())
 }
 
+// ICRC-10 supported standards
+#[query]
+fn icrc10_supported_standards() -> Vec<StandardRecord> {
+    icrc10::standards(&[
+        ("ICRC-10", icrc10::ICRC10_URL),
+        ("ICRC-21", icrc21::ICRC21_URL),
+    ])
+}
+
+// ICRC-21 consent messages
+
+fn box_type_name(box_type: &BoxType) -> &'static str {
+    match box_type {
+        BoxType::Common => "Common",
+        BoxType::Rare => "Rare",
+        BoxType::Epic => "Epic",
+        BoxType::Legendary => "Legendary",
+    }
+}
+
+#[update]
+fn icrc21_canister_call_consent_message(
+    request: ConsentMessageRequest,
+) -> Result<ConsentInfo, Icrc21Error> {
+    if request.method != "open_mystery_box" {
+        return Err(Icrc21Error::unsupported(&request.method));
+    }
+
+    let args: OpenBoxArgs = candid::decode_one(&request.arg)
+        .map_err(|err| Icrc21Error::bad_arg(&request.method, err))?;
+    let mystery_box = MYSTERY_BOXES.with(|boxes| boxes.borrow().get(&args.box_id))
+        .ok_or_else(|| Icrc21Error::unavailable("Mystery box not found"))?;
+    let GameTokenInfo {
+        symbol, decimals, ..
+    } = game_token().ok_or_else(|| Icrc21Error::unavailable("Game token canister not set"))?;
+
+    let price = format!("{} {}", format_amount(&mystery_box.price, decimals), symbol);
+    let title = format!(
+        "Open {} box #{} for {}",
+        box_type_name(&mystery_box.box_type),
+        mystery_box.id,
+        price
+    );
+    let fields = vec![
+        ("Price", price),
+        (
+            "Payment",
+            format!("Collected from your {} approval to this canister", symbol),
+        ),
+        (
+            "Boxes left",
+            format!("{} of {}", mystery_box.available_count, mystery_box.total_count),
+        ),
+    ];
+
+    Ok(consent_info(&request.user_preferences, &title, &fields))
+}
+
 // Helper function to create default card templates
 pub fn create_default_card_templates() -> Vec<RarityWeight> {
     vec![
@@ -0,0 +1,135 @@
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api::time;
+use ic_cdk::{caller, update};
+
+use crate::icrc3::{Operation, Transaction};
+use crate::{
+    batch_size_error, check_created_at_time, find_duplicate, icrc37, memo_too_long,
+    mutate_settings, read_settings, record_transaction, remove_token, Account, TimeError, OWNERS,
+};
+
+#[derive(CandidType, Deserialize)]
+pub struct BurnArg {
+    // Subaccount of the approved operator; ignored when the owner burns
+    pub spender_subaccount: Option<[u8; 32]>,
+    pub from: Account,
+    pub token_id: Nat,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum BurnError {
+    NonExistingTokenId,
+    Unauthorized,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum BurnResult {
+    Ok(Nat),
+    Err(BurnError),
+}
+
+fn burn(arg: BurnArg, now: u64) -> Result<Nat, BurnError> {
+    if let Some(message) = memo_too_long(&arg.memo) {
+        return Err(BurnError::GenericError {
+            error_code: Nat::from(1u64),
+            message,
+        });
+    }
+    check_created_at_time(arg.created_at_time, now).map_err(|err| match err {
+        TimeError::TooOld => BurnError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => BurnError::CreatedInFuture { ledger_time },
+    })?;
+
+    match OWNERS.with(|owners| owners.borrow().get(&arg.token_id)) {
+        None => return Err(BurnError::NonExistingTokenId),
+        Some(current_owner) if current_owner != arg.from => return Err(BurnError::Unauthorized),
+        Some(_) => {}
+    }
+
+    // The owner burns directly; anyone else needs an ICRC-37 approval
+    let spender = if arg.from.owner == caller() {
+        None
+    } else {
+        let spender = Account {
+            owner: caller(),
+            subaccount: arg.spender_subaccount,
+        };
+        if !icrc37::is_approved_for(&spender, &arg.from, &arg.token_id, now) {
+            return Err(BurnError::Unauthorized);
+        }
+        Some(spender)
+    };
+
+    let token_id = arg.token_id.clone();
+    let tx = Transaction {
+        operation: Operation::Burn {
+            token_id: arg.token_id,
+            from: arg.from,
+            spender,
+        },
+        memo: arg.memo,
+        created_at_time: arg.created_at_time,
+    };
+    if let Some(duplicate_of) = find_duplicate(&tx, now) {
+        return Err(BurnError::Duplicate { duplicate_of });
+    }
+
+    remove_token(&token_id);
+    Ok(record_transaction(tx))
+}
+
+/// Destroys cards owned by the caller, or by accounts that approved the
+/// caller through ICRC-37. Each entry succeeds or fails on its own; the game
+/// engine is then told which cards are gone and who owned them.
+#[update]
+fn icrc7_burn(args: Vec<BurnArg>) -> Vec<Option<BurnResult>> {
+    if let Some((error_code, message)) = batch_size_error(args.len()) {
+        return vec![Some(BurnResult::Err(BurnError::GenericBatchError {
+            error_code,
+            message,
+        }))];
+    }
+    let now = time();
+
+    let mut burned = Vec::new();
+    let results = args
+        .into_iter()
+        .map(|arg| {
+            let token_id = arg.token_id.clone();
+            let owner = arg.from.owner;
+            Some(match burn(arg, now) {
+                Ok(index) => {
+                    burned.push((token_id, owner));
+                    BurnResult::Ok(index)
+                }
+                Err(err) => BurnResult::Err(err),
+            })
+        })
+        .collect();
+
+    if let Some(game_engine) = read_settings(|settings| settings.game_engine_canister) {
+        if !burned.is_empty() {
+            // One-way call: a failing hook must not undo the burn
+            let _ = ic_cdk::notify(game_engine, "remove_burned_cards", (burned,));
+        }
+    }
+
+    results
+}
+
+#[update]
+fn set_game_engine_canister(canister_id: Option<Principal>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err("Unauthorized".to_string());
+    }
+
+    mutate_settings(|settings| settings.game_engine_canister = canister_id);
+    Ok(())
+}
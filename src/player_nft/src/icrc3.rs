@@ -0,0 +1,180 @@
+use candid::Nat;
+
+pub use icrc_common::icrc3::{
+    tip_hash_tree, tip_root_hash, ArchiveInfo, BlockWithId, DataCertificate, GetArchivesArgs,
+    GetBlocksArgs, GetBlocksResult, SupportedBlockType,
+};
+
+use crate::{Account, Value};
+
+// Collection operations recorded in the block log
+#[derive(Clone)]
+pub enum Operation {
+    Mint {
+        token_id: Nat,
+        to: Account,
+        meta: Value,
+    },
+    Transfer {
+        token_id: Nat,
+        from: Account,
+        to: Account,
+        spender: Option<Account>,
+    },
+    // Burn by the owner, or by an approved operator when `spender` is set
+    Burn {
+        token_id: Nat,
+        from: Account,
+        spender: Option<Account>,
+    },
+    // Approval of a single token, or of the whole collection when unset
+    Approve {
+        token_id: Option<Nat>,
+        from: Account,
+        spender: Account,
+        expires_at: Option<u64>,
+    },
+    // Revocation of one spender, or of every spender when unset
+    Revoke {
+        token_id: Option<Nat>,
+        from: Account,
+        spender: Option<Account>,
+    },
+}
+
+impl Operation {
+    /// Token whose history includes this operation.
+    pub fn token_id(&self) -> Option<&Nat> {
+        match self {
+            Operation::Mint { token_id, .. }
+            | Operation::Transfer { token_id, .. }
+            | Operation::Burn { token_id, .. } => Some(token_id),
+            Operation::Approve { token_id, .. } | Operation::Revoke { token_id, .. } => {
+                token_id.as_ref()
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Transaction {
+    pub operation: Operation,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+impl Transaction {
+    fn block_type(&self) -> &'static str {
+        match &self.operation {
+            Operation::Mint { .. } => "7mint",
+            Operation::Transfer { spender: None, .. } => "7xfer",
+            Operation::Transfer {
+                spender: Some(_), ..
+            } => "37xfer",
+            Operation::Burn { .. } => "7burn",
+            Operation::Approve {
+                token_id: Some(_), ..
+            } => "37approve",
+            Operation::Approve { token_id: None, .. } => "37approve_coll",
+            Operation::Revoke {
+                token_id: Some(_), ..
+            } => "37revoke",
+            Operation::Revoke { token_id: None, .. } => "37revoke_coll",
+        }
+    }
+
+    fn tx_value(&self) -> Value {
+        let mut tx = Vec::new();
+        if let Some(token_id) = self.operation.token_id() {
+            tx.push(("tid".to_string(), Value::Nat(token_id.clone())));
+        }
+        match &self.operation {
+            Operation::Mint { to, meta, .. } => {
+                tx.push(("to".to_string(), Value::from(to)));
+                tx.push(("meta".to_string(), meta.clone()));
+            }
+            Operation::Transfer {
+                from, to, spender, ..
+            } => {
+                if let Some(spender) = spender {
+                    tx.push(("spender".to_string(), Value::from(spender)));
+                }
+                tx.push(("from".to_string(), Value::from(from)));
+                tx.push(("to".to_string(), Value::from(to)));
+            }
+            Operation::Burn { from, spender, .. } => {
+                tx.push(("from".to_string(), Value::from(from)));
+                if let Some(spender) = spender {
+                    tx.push(("spender".to_string(), Value::from(spender)));
+                }
+            }
+            Operation::Approve {
+                from,
+                spender,
+                expires_at,
+                ..
+            } => {
+                tx.push(("from".to_string(), Value::from(from)));
+                tx.push(("spender".to_string(), Value::from(spender)));
+                if let Some(expires_at) = expires_at {
+                    tx.push(("exp".to_string(), Value::Nat(Nat::from(*expires_at))));
+                }
+            }
+            Operation::Revoke { from, spender, .. } => {
+                tx.push(("from".to_string(), Value::from(from)));
+                if let Some(spender) = spender {
+                    tx.push(("spender".to_string(), Value::from(spender)));
+                }
+            }
+        }
+        if let Some(memo) = &self.memo {
+            tx.push(("memo".to_string(), Value::Blob(memo.clone())));
+        }
+        if let Some(created_at_time) = self.created_at_time {
+            tx.push(("ts".to_string(), Value::Nat(Nat::from(created_at_time))));
+        }
+        Value::Map(tx)
+    }
+
+    /// Hash identifying the transaction intent, used for deduplication.
+    pub fn hash(&self) -> [u8; 32] {
+        self.tx_value().hash()
+    }
+
+    /// Encodes the transaction as an ICRC-3 block chained to `phash`.
+    pub fn to_block(&self, phash: Option<[u8; 32]>, timestamp: u64) -> Value {
+        let mut block = vec![
+            (
+                "btype".to_string(),
+                Value::Text(self.block_type().to_string()),
+            ),
+            ("ts".to_string(), Value::Nat(Nat::from(timestamp))),
+            ("tx".to_string(), self.tx_value()),
+        ];
+        if let Some(phash) = phash {
+            block.push(("phash".to_string(), Value::Blob(phash.to_vec())));
+        }
+        Value::Map(block)
+    }
+}
+
+pub fn supported_block_types() -> Vec<SupportedBlockType> {
+    let icrc7 = "https://github.com/dfinity/ICRC/blob/main/ICRCs/ICRC-7/ICRC-7.md";
+    let icrc37 = "https://github.com/dfinity/ICRC/blob/main/ICRCs/ICRC-37/ICRC-37.md";
+    vec![
+        ("7mint", icrc7),
+        ("7xfer", icrc7),
+        ("7burn", icrc7),
+        ("37approve", icrc37),
+        ("37approve_coll", icrc37),
+        ("37revoke", icrc37),
+        ("37revoke_coll", icrc37),
+        ("37xfer", icrc37),
+    ]
+    .into_iter()
+    .map(|(block_type, url)| SupportedBlockType {
+        block_type: block_type.to_string(),
+        url: url.to_string(),
+    })
+    .collect()
+}
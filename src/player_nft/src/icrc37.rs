@@ -0,0 +1,728 @@
+use candid::{CandidType, Deserialize, Nat};
+use ic_cdk::api::time;
+use ic_cdk::{caller, query, update};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::ops::Bound;
+
+use crate::icrc3::{Operation, Transaction};
+use crate::{
+    apply_transfer, batch_size_error, check_created_at_time, find_duplicate, memo_too_long,
+    record_transaction, take_limit, Account, Memory, TimeError, MEMORY_MANAGER, OWNERS,
+};
+
+// ICRC-37 limits
+pub const MAX_APPROVALS_PER_TOKEN_OR_COLLECTION: u64 = 10;
+pub const MAX_REVOKE_APPROVALS: u64 = 20;
+
+// ICRC-37 approval types
+#[derive(CandidType, Deserialize, Serialize, Clone)]
+pub struct ApprovalInfo {
+    pub spender: Account,
+    pub from_subaccount: Option<[u8; 32]>,
+    pub expires_at: Option<u64>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ApproveTokenArg {
+    pub token_id: Nat,
+    pub approval_info: ApprovalInfo,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum ApproveTokenError {
+    InvalidSpender,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum ApproveTokenResult {
+    Ok(Nat),
+    Err(ApproveTokenError),
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ApproveCollectionArg {
+    pub approval_info: ApprovalInfo,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum ApproveCollectionError {
+    InvalidSpender,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum ApproveCollectionResult {
+    Ok(Nat),
+    Err(ApproveCollectionError),
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct RevokeTokenApprovalArg {
+    // Revokes every approval of the token when unset
+    pub spender: Option<Account>,
+    pub from_subaccount: Option<[u8; 32]>,
+    pub token_id: Nat,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum RevokeTokenApprovalError {
+    ApprovalDoesNotExist,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum RevokeTokenApprovalResponse {
+    Ok(Nat),
+    Err(RevokeTokenApprovalError),
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct RevokeCollectionApprovalArg {
+    // Revokes every collection approval of the caller when unset
+    pub spender: Option<Account>,
+    pub from_subaccount: Option<[u8; 32]>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum RevokeCollectionApprovalError {
+    ApprovalDoesNotExist,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum RevokeCollectionApprovalResult {
+    Ok(Nat),
+    Err(RevokeCollectionApprovalError),
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct IsApprovedArg {
+    pub spender: Account,
+    pub from_subaccount: Option<[u8; 32]>,
+    pub token_id: Nat,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct TokenApproval {
+    pub token_id: Nat,
+    pub approval_info: ApprovalInfo,
+}
+
+pub type CollectionApproval = ApprovalInfo;
+
+#[derive(CandidType, Deserialize)]
+pub struct TransferFromArg {
+    pub spender_subaccount: Option<[u8; 32]>,
+    pub from: Account,
+    pub to: Account,
+    pub token_id: Nat,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum TransferFromError {
+    InvalidRecipient,
+    Unauthorized,
+    NonExistingTokenId,
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum TransferFromResult {
+    Ok(Nat),
+    Err(TransferFromError),
+}
+
+thread_local! {
+    // Accounts in approval keys are stored in canonical form
+
+    // (token id, spender) -> approval granted by the token's current owner
+    static TOKEN_APPROVALS: RefCell<StableBTreeMap<(Nat, Account), ApprovalInfo, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
+        )
+    );
+
+    // (owner, spender) -> approval over every token the owner holds
+    static COLLECTION_APPROVALS: RefCell<StableBTreeMap<(Account, Account), ApprovalInfo, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
+        )
+    );
+}
+
+fn is_live(approval: &ApprovalInfo, now: u64) -> bool {
+    approval
+        .expires_at
+        .map_or(true, |expires_at| expires_at > now)
+}
+
+fn min_account() -> Account {
+    Account::from(candid::Principal::from_slice(&[]))
+}
+
+fn live_token_approvals(token_id: &Nat, now: u64) -> Vec<(Account, ApprovalInfo)> {
+    TOKEN_APPROVALS.with(|approvals| {
+        approvals
+            .borrow()
+            .range((token_id.clone(), min_account())..)
+            .take_while(|((id, _), _)| id == token_id)
+            .filter(|(_, approval)| is_live(approval, now))
+            .map(|((_, spender), approval)| (spender, approval))
+            .collect()
+    })
+}
+
+/// Drops every token-level approval of `token_id`. Called whenever the token
+/// changes hands, since approvals never carry over to the next owner.
+pub fn clear_token_approvals(token_id: &Nat) {
+    TOKEN_APPROVALS.with(|approvals| {
+        let mut approvals = approvals.borrow_mut();
+        let keys: Vec<(Nat, Account)> = approvals
+            .range((token_id.clone(), min_account())..)
+            .take_while(|((id, _), _)| id == token_id)
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            approvals.remove(&key);
+        }
+    });
+}
+
+/// Whether `spender` may transfer `token_id` out of `owner`, either through
+/// an approval of the token or of the owner's whole collection.
+pub fn is_approved_for(spender: &Account, owner: &Account, token_id: &Nat, now: u64) -> bool {
+    let token_approval = TOKEN_APPROVALS
+        .with(|approvals| approvals.borrow().get(&(token_id.clone(), spender.clone())));
+    if token_approval.map_or(false, |approval| is_live(&approval, now)) {
+        return true;
+    }
+
+    COLLECTION_APPROVALS
+        .with(|approvals| approvals.borrow().get(&(owner.clone(), spender.clone())))
+        .map_or(false, |approval| is_live(&approval, now))
+}
+
+fn approve_token(arg: ApproveTokenArg, now: u64) -> Result<Nat, ApproveTokenError> {
+    let info = arg.approval_info;
+    let owner = Account {
+        owner: caller(),
+        subaccount: info.from_subaccount,
+    };
+
+    if let Some(message) = memo_too_long(&info.memo) {
+        return Err(ApproveTokenError::GenericError {
+            error_code: Nat::from(1u64),
+            message,
+        });
+    }
+    check_created_at_time(info.created_at_time, now).map_err(|err| match err {
+        TimeError::TooOld => ApproveTokenError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => {
+            ApproveTokenError::CreatedInFuture { ledger_time }
+        }
+    })?;
+    if info.spender.owner == owner.owner {
+        return Err(ApproveTokenError::InvalidSpender);
+    }
+    if info
+        .expires_at
+        .map_or(false, |expires_at| expires_at <= now)
+    {
+        return Err(ApproveTokenError::GenericError {
+            error_code: Nat::from(2u64),
+            message: "Approval has already expired".to_string(),
+        });
+    }
+
+    match OWNERS.with(|owners| owners.borrow().get(&arg.token_id)) {
+        None => return Err(ApproveTokenError::NonExistingTokenId),
+        Some(current_owner) if current_owner != owner => {
+            return Err(ApproveTokenError::Unauthorized)
+        }
+        Some(_) => {}
+    }
+
+    let key = (arg.token_id.clone(), info.spender.clone().canonical());
+    let replaces_existing = TOKEN_APPROVALS.with(|approvals| approvals.borrow().contains_key(&key));
+    if !replaces_existing
+        && live_token_approvals(&arg.token_id, now).len() as u64
+            >= MAX_APPROVALS_PER_TOKEN_OR_COLLECTION
+    {
+        return Err(ApproveTokenError::GenericError {
+            error_code: Nat::from(3u64),
+            message: format!(
+                "A token can have at most {} approvals",
+                MAX_APPROVALS_PER_TOKEN_OR_COLLECTION
+            ),
+        });
+    }
+
+    let tx = Transaction {
+        operation: Operation::Approve {
+            token_id: Some(arg.token_id),
+            from: owner,
+            spender: info.spender.clone(),
+            expires_at: info.expires_at,
+        },
+        memo: info.memo.clone(),
+        created_at_time: info.created_at_time,
+    };
+    TOKEN_APPROVALS.with(|approvals| approvals.borrow_mut().insert(key, info));
+    Ok(record_transaction(tx))
+}
+
+/// Approves spenders for individual tokens. Approving the same spender again
+/// replaces its previous approval.
+#[update]
+fn icrc37_approve_tokens(args: Vec<ApproveTokenArg>) -> Vec<Option<ApproveTokenResult>> {
+    if let Some((error_code, message)) = batch_size_error(args.len()) {
+        return vec![Some(ApproveTokenResult::Err(
+            ApproveTokenError::GenericBatchError {
+                error_code,
+                message,
+            },
+        ))];
+    }
+    let now = time();
+
+    args.into_iter()
+        .map(|arg| {
+            Some(match approve_token(arg, now) {
+                Ok(index) => ApproveTokenResult::Ok(index),
+                Err(err) => ApproveTokenResult::Err(err),
+            })
+        })
+        .collect()
+}
+
+fn approve_collection(arg: ApproveCollectionArg, now: u64) -> Result<Nat, ApproveCollectionError> {
+    let info = arg.approval_info;
+    let owner = Account {
+        owner: caller(),
+        subaccount: info.from_subaccount,
+    };
+
+    if let Some(message) = memo_too_long(&info.memo) {
+        return Err(ApproveCollectionError::GenericError {
+            error_code: Nat::from(1u64),
+            message,
+        });
+    }
+    check_created_at_time(info.created_at_time, now).map_err(|err| match err {
+        TimeError::TooOld => ApproveCollectionError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => {
+            ApproveCollectionError::CreatedInFuture { ledger_time }
+        }
+    })?;
+    if info.spender.owner == owner.owner {
+        return Err(ApproveCollectionError::InvalidSpender);
+    }
+    if info
+        .expires_at
+        .map_or(false, |expires_at| expires_at <= now)
+    {
+        return Err(ApproveCollectionError::GenericError {
+            error_code: Nat::from(2u64),
+            message: "Approval has already expired".to_string(),
+        });
+    }
+
+    let key = (owner.clone().canonical(), info.spender.clone().canonical());
+    let (replaces_existing, live_count) = COLLECTION_APPROVALS.with(|approvals| {
+        let approvals = approvals.borrow();
+        let live_count = approvals
+            .range((owner.clone(), min_account())..)
+            .take_while(|((approver, _), _)| *approver == owner)
+            .filter(|(_, approval)| is_live(approval, now))
+            .count();
+        (approvals.contains_key(&key), live_count as u64)
+    });
+    if !replaces_existing && live_count >= MAX_APPROVALS_PER_TOKEN_OR_COLLECTION {
+        return Err(ApproveCollectionError::GenericError {
+            error_code: Nat::from(3u64),
+            message: format!(
+                "An account can have at most {} collection approvals",
+                MAX_APPROVALS_PER_TOKEN_OR_COLLECTION
+            ),
+        });
+    }
+
+    let tx = Transaction {
+        operation: Operation::Approve {
+            token_id: None,
+            from: owner,
+            spender: info.spender.clone(),
+            expires_at: info.expires_at,
+        },
+        memo: info.memo.clone(),
+        created_at_time: info.created_at_time,
+    };
+    COLLECTION_APPROVALS.with(|approvals| approvals.borrow_mut().insert(key, info));
+    Ok(record_transaction(tx))
+}
+
+/// Approves spenders for every token held by the caller's account, including
+/// tokens received after the approval.
+#[update]
+fn icrc37_approve_collection(
+    args: Vec<ApproveCollectionArg>,
+) -> Vec<Option<ApproveCollectionResult>> {
+    if let Some((error_code, message)) = batch_size_error(args.len()) {
+        return vec![Some(ApproveCollectionResult::Err(
+            ApproveCollectionError::GenericBatchError {
+                error_code,
+                message,
+            },
+        ))];
+    }
+    let now = time();
+
+    args.into_iter()
+        .map(|arg| {
+            Some(match approve_collection(arg, now) {
+                Ok(index) => ApproveCollectionResult::Ok(index),
+                Err(err) => ApproveCollectionResult::Err(err),
+            })
+        })
+        .collect()
+}
+
+fn revoke_token_approval(
+    arg: RevokeTokenApprovalArg,
+    now: u64,
+) -> Result<Nat, RevokeTokenApprovalError> {
+    let owner = Account {
+        owner: caller(),
+        subaccount: arg.from_subaccount,
+    };
+
+    if let Some(message) = memo_too_long(&arg.memo) {
+        return Err(RevokeTokenApprovalError::GenericError {
+            error_code: Nat::from(1u64),
+            message,
+        });
+    }
+    check_created_at_time(arg.created_at_time, now).map_err(|err| match err {
+        TimeError::TooOld => RevokeTokenApprovalError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => {
+            RevokeTokenApprovalError::CreatedInFuture { ledger_time }
+        }
+    })?;
+
+    match OWNERS.with(|owners| owners.borrow().get(&arg.token_id)) {
+        None => return Err(RevokeTokenApprovalError::NonExistingTokenId),
+        Some(current_owner) if current_owner != owner => {
+            return Err(RevokeTokenApprovalError::Unauthorized)
+        }
+        Some(_) => {}
+    }
+
+    match &arg.spender {
+        Some(spender) => {
+            let removed = TOKEN_APPROVALS.with(|approvals| {
+                approvals
+                    .borrow_mut()
+                    .remove(&(arg.token_id.clone(), spender.clone()))
+            });
+            if !removed.map_or(false, |approval| is_live(&approval, now)) {
+                return Err(RevokeTokenApprovalError::ApprovalDoesNotExist);
+            }
+        }
+        None => {
+            if live_token_approvals(&arg.token_id, now).is_empty() {
+                return Err(RevokeTokenApprovalError::ApprovalDoesNotExist);
+            }
+            clear_token_approvals(&arg.token_id);
+        }
+    }
+
+    Ok(record_transaction(Transaction {
+        operation: Operation::Revoke {
+            token_id: Some(arg.token_id),
+            from: owner,
+            spender: arg.spender,
+        },
+        memo: arg.memo,
+        created_at_time: arg.created_at_time,
+    }))
+}
+
+#[update]
+fn icrc37_revoke_token_approvals(
+    args: Vec<RevokeTokenApprovalArg>,
+) -> Vec<Option<RevokeTokenApprovalResponse>> {
+    if args.len() as u64 > MAX_REVOKE_APPROVALS {
+        ic_cdk::trap(&format!(
+            "At most {} approvals can be revoked at once",
+            MAX_REVOKE_APPROVALS
+        ));
+    }
+    let now = time();
+
+    args.into_iter()
+        .map(|arg| {
+            Some(match revoke_token_approval(arg, now) {
+                Ok(index) => RevokeTokenApprovalResponse::Ok(index),
+                Err(err) => RevokeTokenApprovalResponse::Err(err),
+            })
+        })
+        .collect()
+}
+
+fn revoke_collection_approval(
+    arg: RevokeCollectionApprovalArg,
+    now: u64,
+) -> Result<Nat, RevokeCollectionApprovalError> {
+    let owner = Account {
+        owner: caller(),
+        subaccount: arg.from_subaccount,
+    };
+
+    if let Some(message) = memo_too_long(&arg.memo) {
+        return Err(RevokeCollectionApprovalError::GenericError {
+            error_code: Nat::from(1u64),
+            message,
+        });
+    }
+    check_created_at_time(arg.created_at_time, now).map_err(|err| match err {
+        TimeError::TooOld => RevokeCollectionApprovalError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => {
+            RevokeCollectionApprovalError::CreatedInFuture { ledger_time }
+        }
+    })?;
+
+    let revoked = COLLECTION_APPROVALS.with(|approvals| {
+        let mut approvals = approvals.borrow_mut();
+        let keys: Vec<(Account, Account)> = match &arg.spender {
+            Some(spender) => vec![(owner.clone(), spender.clone())],
+            None => approvals
+                .range((owner.clone(), min_account())..)
+                .take_while(|((approver, _), _)| *approver == owner)
+                .map(|(key, _)| key)
+                .collect(),
+        };
+        keys.into_iter()
+            .filter_map(|key| approvals.remove(&key))
+            .filter(|approval| is_live(approval, now))
+            .count()
+    });
+    if revoked == 0 {
+        return Err(RevokeCollectionApprovalError::ApprovalDoesNotExist);
+    }
+
+    Ok(record_transaction(Transaction {
+        operation: Operation::Revoke {
+            token_id: None,
+            from: owner,
+            spender: arg.spender,
+        },
+        memo: arg.memo,
+        created_at_time: arg.created_at_time,
+    }))
+}
+
+#[update]
+fn icrc37_revoke_collection_approvals(
+    args: Vec<RevokeCollectionApprovalArg>,
+) -> Vec<Option<RevokeCollectionApprovalResult>> {
+    if args.len() as u64 > MAX_REVOKE_APPROVALS {
+        ic_cdk::trap(&format!(
+            "At most {} approvals can be revoked at once",
+            MAX_REVOKE_APPROVALS
+        ));
+    }
+    let now = time();
+
+    args.into_iter()
+        .map(|arg| {
+            Some(match revoke_collection_approval(arg, now) {
+                Ok(index) => RevokeCollectionApprovalResult::Ok(index),
+                Err(err) => RevokeCollectionApprovalResult::Err(err),
+            })
+        })
+        .collect()
+}
+
+#[query]
+fn icrc37_max_approvals_per_token_or_collection() -> Option<Nat> {
+    Some(Nat::from(MAX_APPROVALS_PER_TOKEN_OR_COLLECTION))
+}
+
+#[query]
+fn icrc37_max_revoke_approvals() -> Option<Nat> {
+    Some(Nat::from(MAX_REVOKE_APPROVALS))
+}
+
+#[query]
+fn icrc37_is_approved(args: Vec<IsApprovedArg>) -> Vec<bool> {
+    let now = time();
+    args.into_iter()
+        .map(|arg| {
+            let owner = OWNERS.with(|owners| owners.borrow().get(&arg.token_id));
+            owner.map_or(false, |owner| {
+                owner.effective_subaccount() == arg.from_subaccount.unwrap_or([0; 32])
+                    && is_approved_for(&arg.spender, &owner, &arg.token_id, now)
+            })
+        })
+        .collect()
+}
+
+/// Live approvals of `token_id` ordered by spender, starting after `prev`.
+#[query]
+fn icrc37_get_token_approvals(
+    token_id: Nat,
+    prev: Option<TokenApproval>,
+    take: Option<Nat>,
+) -> Vec<TokenApproval> {
+    let now = time();
+    let start = match prev {
+        Some(prev) => Bound::Excluded((token_id.clone(), prev.approval_info.spender)),
+        None => Bound::Included((token_id.clone(), min_account())),
+    };
+
+    TOKEN_APPROVALS.with(|approvals| {
+        approvals
+            .borrow()
+            .range((start, Bound::Unbounded))
+            .take_while(|((id, _), _)| *id == token_id)
+            .filter(|(_, approval)| is_live(approval, now))
+            .take(take_limit(take))
+            .map(|((token_id, _), approval_info)| TokenApproval {
+                token_id,
+                approval_info,
+            })
+            .collect()
+    })
+}
+
+/// Live collection approvals granted by `owner` ordered by spender, starting
+/// after `prev`.
+#[query]
+fn icrc37_get_collection_approvals(
+    owner: Account,
+    prev: Option<CollectionApproval>,
+    take: Option<Nat>,
+) -> Vec<CollectionApproval> {
+    let now = time();
+    let start = match prev {
+        Some(prev) => Bound::Excluded((owner.clone(), prev.spender)),
+        None => Bound::Included((owner.clone(), min_account())),
+    };
+
+    COLLECTION_APPROVALS.with(|approvals| {
+        approvals
+            .borrow()
+            .range((start, Bound::Unbounded))
+            .take_while(|((approver, _), _)| *approver == owner)
+            .filter(|(_, approval)| is_live(approval, now))
+            .take(take_limit(take))
+            .map(|(_, approval)| approval)
+            .collect()
+    })
+}
+
+fn transfer_from(arg: TransferFromArg, now: u64) -> Result<Nat, TransferFromError> {
+    let spender = Account {
+        owner: caller(),
+        subaccount: arg.spender_subaccount,
+    };
+
+    if let Some(message) = memo_too_long(&arg.memo) {
+        return Err(TransferFromError::GenericError {
+            error_code: Nat::from(1u64),
+            message,
+        });
+    }
+    check_created_at_time(arg.created_at_time, now).map_err(|err| match err {
+        TimeError::TooOld => TransferFromError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => {
+            TransferFromError::CreatedInFuture { ledger_time }
+        }
+    })?;
+    if arg.to == arg.from {
+        return Err(TransferFromError::InvalidRecipient);
+    }
+
+    match OWNERS.with(|owners| owners.borrow().get(&arg.token_id)) {
+        None => return Err(TransferFromError::NonExistingTokenId),
+        Some(current_owner) if current_owner != arg.from => {
+            return Err(TransferFromError::Unauthorized)
+        }
+        Some(_) => {}
+    }
+    if !is_approved_for(&spender, &arg.from, &arg.token_id, now) {
+        return Err(TransferFromError::Unauthorized);
+    }
+
+    let tx = Transaction {
+        operation: Operation::Transfer {
+            token_id: arg.token_id,
+            from: arg.from,
+            to: arg.to,
+            spender: Some(spender),
+        },
+        memo: arg.memo,
+        created_at_time: arg.created_at_time,
+    };
+    if let Some(duplicate_of) = find_duplicate(&tx, now) {
+        return Err(TransferFromError::Duplicate { duplicate_of });
+    }
+
+    Ok(apply_transfer(tx))
+}
+
+/// Transfers tokens on behalf of their owners. The caller needs an unexpired
+/// approval for the token or for the owner's collection.
+#[update]
+fn icrc37_transfer_from(args: Vec<TransferFromArg>) -> Vec<Option<TransferFromResult>> {
+    if let Some((error_code, message)) = batch_size_error(args.len()) {
+        return vec![Some(TransferFromResult::Err(
+            TransferFromError::GenericBatchError {
+                error_code,
+                message,
+            },
+        ))];
+    }
+    let now = time();
+
+    args.into_iter()
+        .map(|arg| {
+            Some(match transfer_from(arg, now) {
+                Ok(index) => TransferFromResult::Ok(index),
+                Err(err) => TransferFromResult::Err(err),
+            })
+        })
+        .collect()
+}
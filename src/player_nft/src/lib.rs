@@ -1,19 +1,29 @@
 use candid::{CandidType, Deserialize, Nat, Principal};
-use ic_cdk::{caller, id, query, update};
+use ic_cdk::api::{data_certificate, set_certified_data, time};
+use ic_cdk::{caller, id, post_upgrade, query, update};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell};
+use icrc_common::block_log::{check_created_at_time, BlockLog, TimeError, MAX_BLOCKS_PER_RESPONSE};
+use icrc_common::icrc10::{self, StandardRecord};
+use icrc_common::icrc21::{self, consent_info, ConsentInfo, ConsentMessageRequest, Icrc21Error};
 use serde::Serialize;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::ops::Bound;
+
+mod burn;
+mod icrc3;
+mod icrc37;
+
+use icrc3::{
+    ArchiveInfo, BlockWithId, DataCertificate, GetArchivesArgs, GetBlocksArgs, GetBlocksResult,
+    Operation, SupportedBlockType, Transaction,
+};
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 // ICRC-7 NFT types
-#[derive(CandidType, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Account {
-    pub owner: Principal,
-    pub subaccount: Option<[u8; 32]>,
-}
+pub use icrc_common::{Account, Value};
 
 #[derive(CandidType, Deserialize, Serialize, Clone)]
 pub struct PlayerCard {
@@ -27,6 +37,19 @@ pub struct PlayerCard {
     pub image_url: String,
 }
 
+// Collection settings and counters, persisted in a stable cell.
+// Fields must be optional so settings written by an earlier version still
+// decode after an upgrade
+#[derive(CandidType, Deserialize, Serialize, Clone, Default)]
+pub struct CollectionSettings {
+    pub logo: Option<String>,
+    pub atomic_batch_transfers: Option<bool>,
+    // Notified of burned cards so it can drop them from player decks
+    pub game_engine_canister: Option<Principal>,
+    // Ids are never reused, even after the newest card was burned
+    pub next_token_id: Option<Nat>,
+}
+
 #[derive(CandidType, Deserialize)]
 pub struct TransferArgs {
     pub spender_subaccount: Option<[u8; 32]>,
@@ -39,17 +62,37 @@ pub struct TransferArgs {
 
 #[derive(CandidType, Deserialize)]
 pub enum TransferError {
+    NonExistingTokenId,
+    InvalidRecipient,
     Unauthorized,
     TooOld,
-    CreatedInFuture,
+    CreatedInFuture { ledger_time: u64 },
     Duplicate { duplicate_of: Nat },
-    TemporarilyUnavailable,
     GenericError { error_code: Nat, message: String },
+    GenericBatchError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum TransferResult {
+    Ok(Nat),
+    Err(TransferError),
 }
 
 // Collection metadata
 const COLLECTION_NAME: &str = "MetaHead Player Cards";
 const COLLECTION_SYMBOL: &str = "MHPC";
+const COLLECTION_DESCRIPTION: &str = "Player cards for MetaHead game";
+const SUPPLY_CAP: u64 = 100_000;
+
+// ICRC-7 limits
+const MAX_QUERY_BATCH_SIZE: u64 = 100;
+const MAX_UPDATE_BATCH_SIZE: u64 = 20;
+const DEFAULT_TAKE_VALUE: u64 = 100;
+const MAX_TAKE_VALUE: u64 = 1_000;
+const MAX_MEMO_SIZE: u64 = 32;
+
+// error_code of the GenericBatchError returned for oversized batches
+const TOO_MANY_REQUESTS_ERROR_CODE: u64 = 4;
 
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -67,16 +110,256 @@ thread_local! {
         )
     );
 
-    static NEXT_TOKEN_ID: RefCell<Nat> = RefCell::new(Nat::from(1u64));
+    // Memory ids 2 and 3 hold the ICRC-37 approvals; memory id 4 held the
+    // transaction counter that the block log replaced and must not be reused
+
+    // (owner, token id) index over OWNERS, kept in sync by set_owner
+    static OWNER_TOKENS: RefCell<StableBTreeMap<(Account, Nat), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    // ICRC-3 block log (index memory, data memory) and the transaction
+    // hashes used for deduplication
+    static BLOCK_LOG: RefCell<BlockLog<Memory>> = RefCell::new(
+        BlockLog::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+        )
+    );
+
+    // (token id, block index) for every block that touches a token
+    static TOKEN_BLOCKS: RefCell<StableBTreeMap<(Nat, u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        )
+    );
+
+    static SETTINGS: RefCell<StableCell<CollectionSettings, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+            CollectionSettings::default(),
+        ).expect("failed to initialize the collection settings")
+    );
+}
+
+fn read_settings<R>(f: impl FnOnce(&CollectionSettings) -> R) -> R {
+    SETTINGS.with(|settings| f(settings.borrow().get()))
+}
+
+fn mutate_settings<R>(f: impl FnOnce(&mut CollectionSettings) -> R) -> R {
+    SETTINGS.with(|settings| {
+        let mut cell = settings.borrow_mut();
+        let mut collection_settings = cell.get().clone();
+        let result = f(&mut collection_settings);
+        cell.set(collection_settings)
+            .expect("failed to persist the collection settings");
+        result
+    })
+}
+
+/// Allocates the id of a newly minted card. Collections that predate the
+/// persisted counter resume after the highest id in the tokens or history.
+fn next_token_id() -> Nat {
+    mutate_settings(|settings| {
+        let id = settings.next_token_id.clone().unwrap_or_else(|| {
+            let highest_token = TOKENS.with(|tokens| tokens.borrow().last_key_value());
+            let highest_in_history =
+                TOKEN_BLOCKS.with(|token_blocks| token_blocks.borrow().last_key_value());
+            highest_token
+                .map(|(id, _)| id)
+                .max(highest_in_history.map(|((id, _), _)| id))
+                .map_or(Nat::from(1u64), |id| id + Nat::from(1u64))
+        });
+        settings.next_token_id = Some(id.clone() + Nat::from(1u64));
+        id
+    })
+}
+
+/// Records `owner` as the owner of `token_id`, moving the token between
+/// accounts in the owner index. Owners are stored in canonical form so both
+/// spellings of the default subaccount share one index range.
+fn set_owner(token_id: &Nat, owner: Account) {
+    let owner = owner.canonical();
+    let previous =
+        OWNERS.with(|owners| owners.borrow_mut().insert(token_id.clone(), owner.clone()));
+    OWNER_TOKENS.with(|index| {
+        let mut index = index.borrow_mut();
+        if let Some(previous) = previous {
+            index.remove(&(previous, token_id.clone()));
+        }
+        index.insert((owner, token_id.clone()), ());
+    });
+}
+
+/// Deletes a burned token along with its owner entries and approvals. Its
+/// block history is kept.
+fn remove_token(token_id: &Nat) {
+    TOKENS.with(|tokens| tokens.borrow_mut().remove(token_id));
+    if let Some(owner) = OWNERS.with(|owners| owners.borrow_mut().remove(token_id)) {
+        OWNER_TOKENS.with(|index| index.borrow_mut().remove(&(owner, token_id.clone())));
+    }
+    icrc37::clear_token_approvals(token_id);
+}
+
+/// Number of tokens owned by `account`.
+fn owned_count(account: &Account) -> u64 {
+    OWNER_TOKENS.with(|index| {
+        index
+            .borrow()
+            .range((account.clone(), Nat::from(0u64))..)
+            .take_while(|((owner, _), _)| owner == account)
+            .count() as u64
+    })
+}
+
+/// Page size for a paginated query: `take`, or the default when unset,
+/// capped at the collection's max_take_value.
+fn take_limit(take: Option<Nat>) -> usize {
+    let take = take.unwrap_or(Nat::from(DEFAULT_TAKE_VALUE));
+    take.min(Nat::from(MAX_TAKE_VALUE))
+        .0
+        .try_into()
+        .unwrap_or(MAX_TAKE_VALUE as usize)
+}
+
+/// Rebuilds the owner index from OWNERS when the two have drifted apart,
+/// e.g. on the first upgrade after the index was introduced.
+fn rebuild_owner_index() {
+    let owned = OWNERS.with(|owners| owners.borrow().len());
+    let indexed = OWNER_TOKENS.with(|index| index.borrow().len());
+    if owned == indexed {
+        return;
+    }
+
+    OWNER_TOKENS.with(|index| {
+        let mut index = index.borrow_mut();
+        let stale: Vec<(Account, Nat)> = index.iter().map(|(key, _)| key).collect();
+        for key in stale {
+            index.remove(&key);
+        }
+        OWNERS.with(|owners| {
+            for (token_id, owner) in owners.borrow().iter() {
+                index.insert((owner.canonical(), token_id), ());
+            }
+        });
+    });
+}
+
+#[post_upgrade]
+fn post_upgrade() {
+    rebuild_owner_index();
+
+    // Certified data does not survive upgrades
+    if let Some((index, block)) = last_block() {
+        set_certified_data(&icrc3::tip_root_hash(index, &block.hash()));
+    }
+}
+
+fn memo_too_long(memo: &Option<Vec<u8>>) -> Option<String> {
+    match memo {
+        Some(memo) if memo.len() as u64 > MAX_MEMO_SIZE => {
+            Some(format!("Memo must not exceed {} bytes", MAX_MEMO_SIZE))
+        }
+        _ => None,
+    }
+}
+
+/// Error code and message of the GenericBatchError answering a batch larger
+/// than `icrc7_max_update_batch_size`. Per ICRC-7, an error concerning the
+/// whole batch is returned as the only entry of the response.
+fn batch_size_error(len: usize) -> Option<(Nat, String)> {
+    (len as u64 > MAX_UPDATE_BATCH_SIZE).then(|| {
+        (
+            Nat::from(TOO_MANY_REQUESTS_ERROR_CODE),
+            format!(
+                "At most {} entries can be submitted at once",
+                MAX_UPDATE_BATCH_SIZE
+            ),
+        )
+    })
+}
+
+// Transaction deduplication
+
+/// Index of an identical transaction recorded within the deduplication
+/// window. Transactions without `created_at_time` are never deduplicated.
+fn find_duplicate(tx: &Transaction, now: u64) -> Option<Nat> {
+    tx.created_at_time?;
+    BLOCK_LOG
+        .with(|log| log.borrow_mut().find_duplicate(&tx.hash(), now))
+        .map(Nat::from)
+}
+
+// ICRC-3 block log
+
+fn last_block() -> Option<(u64, Value)> {
+    BLOCK_LOG.with(|log| log.borrow().last_block())
+}
+
+/// Appends the transaction to the block log, re-certifies the chain tip and
+/// returns the new block index.
+fn record_transaction(tx: Transaction) -> Nat {
+    let phash = last_block().map(|(_, block)| block.hash());
+    let block = tx.to_block(phash, time());
+    let block_hash = block.hash();
+    let dedup_key = tx
+        .created_at_time
+        .map(|created_at_time| (created_at_time, tx.hash()));
+    let index = BLOCK_LOG.with(|log| log.borrow_mut().append(&block, dedup_key));
+
+    if let Some(token_id) = tx.operation.token_id() {
+        TOKEN_BLOCKS.with(|token_blocks| {
+            token_blocks
+                .borrow_mut()
+                .insert((token_id.clone(), index), ())
+        });
+    }
+
+    set_certified_data(&icrc3::tip_root_hash(index, &block_hash));
+    Nat::from(index)
 }
 
 #[query]
-fn icrc7_collection_metadata() -> Vec<(String, candid::CandidType)> {
-    vec![
-        ("icrc7:name".to_string(), candid::CandidType::Text(COLLECTION_NAME.to_string())),
-        ("icrc7:symbol".to_string(), candid::CandidType::Text(COLLECTION_SYMBOL.to_string())),
-        ("icrc7:description".to_string(), candid::CandidType::Text("Player cards for MetaHead game".to_string())),
-    ]
+fn icrc7_collection_metadata() -> Vec<(String, Value)> {
+    let mut metadata = vec![
+        ("icrc7:name", Value::Text(COLLECTION_NAME.to_string())),
+        ("icrc7:symbol", Value::Text(COLLECTION_SYMBOL.to_string())),
+        (
+            "icrc7:description",
+            Value::Text(COLLECTION_DESCRIPTION.to_string()),
+        ),
+        ("icrc7:total_supply", Value::Nat(icrc7_total_supply())),
+        ("icrc7:supply_cap", Value::Nat(Nat::from(SUPPLY_CAP))),
+        (
+            "icrc7:max_query_batch_size",
+            Value::Nat(Nat::from(MAX_QUERY_BATCH_SIZE)),
+        ),
+        (
+            "icrc7:max_update_batch_size",
+            Value::Nat(Nat::from(MAX_UPDATE_BATCH_SIZE)),
+        ),
+        (
+            "icrc7:default_take_value",
+            Value::Nat(Nat::from(DEFAULT_TAKE_VALUE)),
+        ),
+        (
+            "icrc7:max_take_value",
+            Value::Nat(Nat::from(MAX_TAKE_VALUE)),
+        ),
+        ("icrc7:max_memo_size", Value::Nat(Nat::from(MAX_MEMO_SIZE))),
+    ];
+    if let Some(logo) = icrc7_logo() {
+        metadata.push(("icrc7:logo", Value::Text(logo)));
+    }
+
+    metadata
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
 }
 
 #[query]
@@ -89,6 +372,46 @@ fn icrc7_symbol() -> String {
     COLLECTION_SYMBOL.to_string()
 }
 
+#[query]
+fn icrc7_description() -> Option<String> {
+    Some(COLLECTION_DESCRIPTION.to_string())
+}
+
+#[query]
+fn icrc7_logo() -> Option<String> {
+    read_settings(|settings| settings.logo.clone())
+}
+
+#[query]
+fn icrc7_supply_cap() -> Option<Nat> {
+    Some(Nat::from(SUPPLY_CAP))
+}
+
+#[query]
+fn icrc7_max_query_batch_size() -> Option<Nat> {
+    Some(Nat::from(MAX_QUERY_BATCH_SIZE))
+}
+
+#[query]
+fn icrc7_max_update_batch_size() -> Option<Nat> {
+    Some(Nat::from(MAX_UPDATE_BATCH_SIZE))
+}
+
+#[query]
+fn icrc7_default_take_value() -> Option<Nat> {
+    Some(Nat::from(DEFAULT_TAKE_VALUE))
+}
+
+#[query]
+fn icrc7_max_take_value() -> Option<Nat> {
+    Some(Nat::from(MAX_TAKE_VALUE))
+}
+
+#[query]
+fn icrc7_max_memo_size() -> Option<Nat> {
+    Some(Nat::from(MAX_MEMO_SIZE))
+}
+
 #[query]
 fn icrc7_total_supply() -> Nat {
     TOKENS.with(|tokens| {
@@ -98,92 +421,266 @@ fn icrc7_total_supply() -> Nat {
 
 #[query]
 fn icrc7_tokens(prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
+    // Token ids are resumed strictly after `prev`, so a walk neither skips
+    // nor repeats ids even when tokens are minted or burned between pages
+    let start = match prev {
+        Some(prev) => Bound::Excluded(prev),
+        None => Bound::Unbounded,
+    };
+
     TOKENS.with(|tokens| {
-        let tokens = tokens.borrow();
-        let start = prev.unwrap_or(Nat::from(0u64));
-        let limit = take.unwrap_or(Nat::from(100u64));
-        
         tokens
-            .iter()
-            .skip(start.0.to_u64_digits()[0] as usize)
-            .take(limit.0.to_u64_digits()[0] as usize)
+            .borrow()
+            .range((start, Bound::Unbounded))
+            .take(take_limit(take))
             .map(|(id, _)| id)
             .collect()
     })
 }
 
+// Token metadata schema, one map per card:
+//   "name"       Text  card name
+//   "image"      Text  card artwork URL
+//   "rarity"     Text  Common, Rare, Epic or Legendary
+//   "attributes" Map   "attack" Nat, "defense" Nat, "speed" Nat,
+//                      "special_ability" Text
+fn card_metadata(card: PlayerCard) -> Vec<(String, Value)> {
+    let attributes = vec![
+        ("attack".to_string(), Value::Nat(Nat::from(card.attack))),
+        ("defense".to_string(), Value::Nat(Nat::from(card.defense))),
+        ("speed".to_string(), Value::Nat(Nat::from(card.speed))),
+        (
+            "special_ability".to_string(),
+            Value::Text(card.special_ability),
+        ),
+    ];
+
+    vec![
+        ("name".to_string(), Value::Text(card.name)),
+        ("image".to_string(), Value::Text(card.image_url)),
+        ("rarity".to_string(), Value::Text(card.rarity)),
+        ("attributes".to_string(), Value::Map(attributes)),
+    ]
+}
+
 #[query]
-fn icrc7_owner_of(token_ids: Vec<Nat>) -> Vec<Option<Account>> {
-    OWNERS.with(|owners| {
-        let owners = owners.borrow();
+fn icrc7_token_metadata(token_ids: Vec<Nat>) -> Vec<Option<Vec<(String, Value)>>> {
+    if token_ids.len() as u64 > MAX_QUERY_BATCH_SIZE {
+        ic_cdk::trap(&format!(
+            "At most {} token ids can be queried at once",
+            MAX_QUERY_BATCH_SIZE
+        ));
+    }
+
+    TOKENS.with(|tokens| {
+        let tokens = tokens.borrow();
         token_ids
             .iter()
-            .map(|id| owners.get(id))
+            .map(|id| tokens.get(id).map(card_metadata))
             .collect()
     })
 }
 
 #[query]
-fn icrc7_balance_of(accounts: Vec<Account>) -> Vec<Nat> {
+fn icrc7_owner_of(token_ids: Vec<Nat>) -> Vec<Option<Account>> {
     OWNERS.with(|owners| {
         let owners = owners.borrow();
-        accounts
+        token_ids
             .iter()
-            .map(|account| {
-                let count = owners
-                    .iter()
-                    .filter(|(_, owner)| *owner == *account)
-                    .count();
-                Nat::from(count as u64)
-            })
+            .map(|id| owners.get(id))
             .collect()
     })
 }
 
+#[query]
+fn icrc7_balance_of(accounts: Vec<Account>) -> Vec<Nat> {
+    accounts
+        .iter()
+        .map(|account| Nat::from(owned_count(account)))
+        .collect()
+}
+
 #[query]
 fn icrc7_tokens_of(account: Account, prev: Option<Nat>, take: Option<Nat>) -> Vec<Nat> {
-    OWNERS.with(|owners| {
-        let owners = owners.borrow();
-        let start = prev.unwrap_or(Nat::from(0u64));
-        let limit = take.unwrap_or(Nat::from(100u64));
-        
-        owners
-            .iter()
-            .filter(|(_, owner)| *owner == account)
-            .skip(start.0.to_u64_digits()[0] as usize)
-            .take(limit.0.to_u64_digits()[0] as usize)
-            .map(|(id, _)| id)
+    // Walks the owner index from `prev`: tokens that stay with the account
+    // are returned exactly once, however the account changes between pages
+    let start = match prev {
+        Some(prev) => Bound::Excluded((account.clone(), prev)),
+        None => Bound::Included((account.clone(), Nat::from(0u64))),
+    };
+
+    OWNER_TOKENS.with(|index| {
+        index
+            .borrow()
+            .range((start, Bound::Unbounded))
+            .take_while(|((owner, _), _)| *owner == account)
+            .take(take_limit(take))
+            .map(|((_, token_id), _)| token_id)
             .collect()
     })
 }
 
+/// Checks a single transfer against the current owners, returning the
+/// transaction to record if it may go ahead.
+fn validate_transfer(
+    caller: Principal,
+    arg: TransferArgs,
+    now: u64,
+) -> Result<Transaction, TransferError> {
+    if let Some(message) = memo_too_long(&arg.memo) {
+        return Err(TransferError::GenericError {
+            error_code: Nat::from(1u64),
+            message,
+        });
+    }
+    check_created_at_time(arg.created_at_time, now).map_err(|err| match err {
+        TimeError::TooOld => TransferError::TooOld,
+        TimeError::CreatedInFuture { ledger_time } => {
+            TransferError::CreatedInFuture { ledger_time }
+        }
+    })?;
+
+    // The caller moves tokens out of its own account; `spender_subaccount`,
+    // when set, has to name the same subaccount as `from`
+    let spender_matches = arg.spender_subaccount.map_or(true, |subaccount| {
+        subaccount == arg.from.effective_subaccount()
+    });
+    if arg.from.owner != caller || !spender_matches {
+        return Err(TransferError::Unauthorized);
+    }
+    if arg.to == arg.from {
+        return Err(TransferError::InvalidRecipient);
+    }
+    match OWNERS.with(|owners| owners.borrow().get(&arg.token_id)) {
+        None => return Err(TransferError::NonExistingTokenId),
+        Some(current_owner) if current_owner != arg.from => {
+            return Err(TransferError::Unauthorized)
+        }
+        Some(_) => {}
+    }
+
+    let tx = Transaction {
+        operation: Operation::Transfer {
+            token_id: arg.token_id,
+            from: arg.from,
+            to: arg.to,
+            spender: None,
+        },
+        memo: arg.memo,
+        created_at_time: arg.created_at_time,
+    };
+    if let Some(duplicate_of) = find_duplicate(&tx, now) {
+        return Err(TransferError::Duplicate { duplicate_of });
+    }
+    Ok(tx)
+}
+
+/// Moves the token of a validated transfer and records the transfer.
+fn apply_transfer(tx: Transaction) -> Nat {
+    let Operation::Transfer { token_id, to, .. } = &tx.operation else {
+        unreachable!("only transfers can be applied as transfers");
+    };
+    set_owner(token_id, to.clone());
+    icrc37::clear_token_approvals(token_id);
+    record_transaction(tx)
+}
+
+/// Transfers tokens out of the caller's accounts. In best-effort mode (the
+/// default) every entry succeeds or fails on its own; with atomic batch
+/// transfers enabled, the whole batch is rejected if any entry fails.
 #[update]
-fn icrc7_transfer(args: Vec<TransferArgs>) -> Vec<Option<TransferError>> {
+fn icrc7_transfer(args: Vec<TransferArgs>) -> Vec<Option<TransferResult>> {
+    if let Some((error_code, message)) = batch_size_error(args.len()) {
+        return vec![Some(TransferResult::Err(
+            TransferError::GenericBatchError {
+                error_code,
+                message,
+            },
+        ))];
+    }
     let caller = caller();
-    
-    args.iter()
-        .map(|arg| {
-            // Check ownership
-            let owner = OWNERS.with(|owners| owners.borrow().get(&arg.token_id));
-            
-            match owner {
-                Some(current_owner) if current_owner.owner == caller => {
-                    // Transfer the token
-                    OWNERS.with(|owners| {
-                        owners.borrow_mut().insert(arg.token_id.clone(), arg.to.clone());
-                    });
-                    None
-                }
-                Some(_) => Some(TransferError::Unauthorized),
-                None => Some(TransferError::GenericError {
-                    error_code: Nat::from(404u64),
-                    message: "Token not found".to_string(),
-                }),
-            }
-        })
+    let now = time();
+
+    if !icrc7_atomic_batch_transfers().unwrap_or(false) {
+        return args
+            .into_iter()
+            .map(|arg| {
+                Some(match validate_transfer(caller, arg, now) {
+                    Ok(tx) => TransferResult::Ok(apply_transfer(tx)),
+                    Err(err) => TransferResult::Err(err),
+                })
+            })
+            .collect();
+    }
+
+    // Entries are validated against the same state, so a token may only
+    // appear once per atomic batch
+    let mut token_ids: Vec<&Nat> = args.iter().map(|arg| &arg.token_id).collect();
+    token_ids.sort();
+    if token_ids.windows(2).any(|pair| pair[0] == pair[1]) {
+        return args
+            .iter()
+            .map(|_| {
+                Some(TransferResult::Err(TransferError::GenericBatchError {
+                    error_code: Nat::from(2u64),
+                    message: "A token can only be transferred once per batch".to_string(),
+                }))
+            })
+            .collect();
+    }
+
+    let validated: Vec<Result<Transaction, TransferError>> = args
+        .into_iter()
+        .map(|arg| validate_transfer(caller, arg, now))
+        .collect();
+    if validated.iter().any(Result::is_err) {
+        return validated
+            .into_iter()
+            .map(|result| {
+                Some(TransferResult::Err(result.err().unwrap_or(
+                    TransferError::GenericBatchError {
+                        error_code: Nat::from(3u64),
+                        message: "Another transfer in the batch failed".to_string(),
+                    },
+                )))
+            })
+            .collect();
+    }
+
+    validated
+        .into_iter()
+        .flatten()
+        .map(|tx| Some(TransferResult::Ok(apply_transfer(tx))))
         .collect()
 }
 
+#[query]
+fn icrc7_atomic_batch_transfers() -> Option<bool> {
+    Some(read_settings(|settings| {
+        settings.atomic_batch_transfers.unwrap_or(false)
+    }))
+}
+
+#[update]
+fn set_atomic_batch_transfers(atomic: bool) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err("Unauthorized".to_string());
+    }
+
+    mutate_settings(|settings| settings.atomic_batch_transfers = Some(atomic));
+    Ok(())
+}
+
+#[update]
+fn set_collection_logo(logo: Option<String>) -> Result<(), String> {
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err("Unauthorized".to_string());
+    }
+
+    mutate_settings(|settings| settings.logo = logo);
+    Ok(())
+}
+
 // Game-specific functions
 #[query]
 fn get_player_card(token_id: Nat) -> Option<PlayerCard> {
@@ -208,11 +705,11 @@ async fn mint_player_card(
         return Err("Unauthorized".to_string());
     }
 
-    let token_id = NEXT_TOKEN_ID.with(|id| {
-        let current = id.borrow().clone();
-        *id.borrow_mut() = current.clone() + Nat::from(1u64);
-        current
-    });
+    if icrc7_total_supply() >= Nat::from(SUPPLY_CAP) {
+        return Err("Supply cap reached".to_string());
+    }
+
+    let token_id = next_token_id();
 
     let card = PlayerCard {
         id: token_id.clone(),
@@ -225,12 +722,20 @@ async fn mint_player_card(
         image_url,
     };
 
+    let meta = Value::Map(card_metadata(card.clone()));
     TOKENS.with(|tokens| {
         tokens.borrow_mut().insert(token_id.clone(), card);
     });
 
-    OWNERS.with(|owners| {
-        owners.borrow_mut().insert(token_id.clone(), to);
+    set_owner(&token_id, to.clone());
+    record_transaction(Transaction {
+        operation: Operation::Mint {
+            token_id: token_id.clone(),
+            to,
+            meta,
+        },
+        memo: None,
+        created_at_time: None,
     });
 
     Ok(token_id)
@@ -246,4 +751,120 @@ fn get_player_cards_by_rarity(rarity: String) -> Vec<PlayerCard> {
             .map(|(_, card)| card)
             .collect()
     })
-} 
\ No newline at end of file
+} 
+
+// ICRC-10 supported standards
+#[query]
+fn icrc10_supported_standards() -> Vec<StandardRecord> {
+    icrc10::standards(&[
+        (
+            "ICRC-7",
+            "https://github.com/dfinity/ICRC/blob/main/ICRCs/ICRC-7/ICRC-7.md",
+        ),
+        (
+            "ICRC-37",
+            "https://github.com/dfinity/ICRC/blob/main/ICRCs/ICRC-37/ICRC-37.md",
+        ),
+        (
+            "ICRC-3",
+            "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-3",
+        ),
+        ("ICRC-10", icrc10::ICRC10_URL),
+        ("ICRC-21", icrc21::ICRC21_URL),
+    ])
+}
+
+// ICRC-21 consent messages
+#[update]
+fn icrc21_canister_call_consent_message(
+    request: ConsentMessageRequest,
+) -> Result<ConsentInfo, Icrc21Error> {
+    if request.method != "icrc7_transfer" {
+        return Err(Icrc21Error::unsupported(&request.method));
+    }
+
+    let args: Vec<TransferArgs> = candid::decode_one(&request.arg)
+        .map_err(|err| Icrc21Error::bad_arg(&request.method, err))?;
+    if args.is_empty() {
+        return Err(Icrc21Error::unavailable("No cards to transfer"));
+    }
+
+    let fields: Vec<(&str, String)> = args
+        .iter()
+        .map(|arg| {
+            let card = match TOKENS.with(|tokens| tokens.borrow().get(&arg.token_id)) {
+                Some(card) => format!("#{} {} ({})", arg.token_id, card.name, card.rarity),
+                None => format!("#{}", arg.token_id),
+            };
+            ("Card", format!("{} to {}", card, arg.to))
+        })
+        .collect();
+    let title = if args.len() == 1 {
+        "Transfer player card".to_string()
+    } else {
+        format!("Transfer {} player cards", args.len())
+    };
+
+    Ok(consent_info(&request.user_preferences, &title, &fields))
+}
+
+// ICRC-3 Standard Functions
+#[query]
+fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    BLOCK_LOG.with(|log| log.borrow().get_blocks(args))
+}
+
+#[query]
+fn icrc3_get_archives(_args: GetArchivesArgs) -> Vec<ArchiveInfo> {
+    // All blocks are kept in this canister's stable memory
+    Vec::new()
+}
+
+#[query]
+fn icrc3_get_tip_certificate() -> Option<DataCertificate> {
+    let certificate = data_certificate()?;
+    let (index, block) = last_block()?;
+
+    Some(DataCertificate {
+        certificate,
+        hash_tree: icrc3::tip_hash_tree(index, &block.hash()),
+    })
+}
+
+#[query]
+fn icrc3_supported_block_types() -> Vec<SupportedBlockType> {
+    icrc3::supported_block_types()
+}
+
+/// Blocks that minted, moved or approved `token_id`, oldest first, from
+/// block index `start` on. Returns at most `length` blocks, capped like
+/// `icrc3_get_blocks`.
+#[query]
+fn get_token_history(token_id: Nat, start: Nat, length: Nat) -> Vec<BlockWithId> {
+    let start = u64::try_from(start.0).unwrap_or(u64::MAX);
+    let length = u64::try_from(length.0)
+        .unwrap_or(u64::MAX)
+        .min(MAX_BLOCKS_PER_RESPONSE);
+
+    let indices: Vec<u64> = TOKEN_BLOCKS.with(|token_blocks| {
+        token_blocks
+            .borrow()
+            .range((token_id.clone(), start)..=(token_id.clone(), u64::MAX))
+            .take(length as usize)
+            .map(|((_, index), _)| index)
+            .collect()
+    });
+
+    BLOCK_LOG.with(|log| log.borrow().blocks_with_ids(indices))
+}
+
+// Textual ICRC-1 accounts
+#[query]
+fn account_to_text(account: Account) -> String {
+    account.to_string()
+}
+
+#[query]
+fn account_from_text(text: String) -> Result<Account, String> {
+    text.parse::<Account>().map_err(|err| err.to_string())
+}